@@ -0,0 +1,49 @@
+#![no_main]
+#![no_std]
+
+extern crate cortex_m_rt;
+extern crate s32k144;
+extern crate s32k144evb;
+
+use cortex_m_rt::entry;
+
+use s32k144evb::{button, gpio, led, wdog};
+
+use s32k144evb::button::{Button, Event};
+use s32k144evb::pcc::Pcc;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = s32k144::Peripherals::take().unwrap();
+
+    let mut wdog_settings = wdog::WatchdogSettings::default();
+    wdog_settings.enable = false;
+    let _wdog = wdog::Watchdog::init(&peripherals.WDOG, wdog_settings);
+
+    let pcc = Pcc::init(&peripherals.PCC);
+    let pcc_portc = pcc.enable_portc().unwrap();
+    let pcc_portd = pcc.enable_portd().unwrap();
+
+    let led = led::RgbLed::init(&peripherals.PTD, &peripherals.PORTD, &pcc_portd);
+    let gpioc = gpio::GpioC::split(&peripherals.PORTC, &peripherals.PTC, &pcc_portc);
+    let mut buttons = button::Buttons::init(gpioc.ptc12, gpioc.ptc13, button::Config::default());
+
+    let mut long_press = false;
+    loop {
+        // Crude tick, the default config expects about 10 ms between samples
+        for _ in 0..10_000 {
+            cortex_m::asm::nop();
+        }
+
+        let events = buttons.tick();
+        if events.sw2 == Some(Event::LongPress) || events.sw3 == Some(Event::LongPress) {
+            long_press = !long_press;
+        }
+
+        led.set(
+            buttons.is_pressed(Button::Sw2),
+            long_press,
+            buttons.is_pressed(Button::Sw3),
+        );
+    }
+}
@@ -8,9 +8,9 @@ extern crate s32k144evb;
 
 use cortex_m_rt::entry;
 
-use s32k144evb::{can, spc, wdog};
+use s32k144evb::{can, sbc, spc, wdog};
 
-use s32k144evb::pcc::Pcc;
+use s32k144evb::pcc::{self, Pcc};
 
 use s32k144evb::can::{CanSettings, ID};
 
@@ -31,6 +31,7 @@ fn main() -> ! {
     let spc_config = spc::Config {
         system_oscillator: spc::SystemOscillatorInput::Crystal(8_000_000),
         soscdiv2: spc::SystemOscillatorOutput::Div1,
+        sircdiv2: spc::SystemOscillatorOutput::Div1,
         ..Default::default()
     };
 
@@ -49,6 +50,19 @@ fn main() -> ! {
     let pcc = Pcc::init(&peripherals.PCC);
     let _pcc_can0 = pcc.enable_can0().unwrap();
     let _pcc_porte = pcc.enable_porte().unwrap();
+    let pcc_portb: pcc::PortB = pcc.enable().unwrap();
+    let pcc_lpspi1: pcc::Lpspi1 = pcc.enable_with_source(pcc::ClockSource::Sircdiv2).unwrap();
+
+    // Don't rely on the state the SBC was left in, the CAN transceiver must be active
+    let mut sbc = sbc::Sbc::init(
+        &peripherals.LPSPI1,
+        &peripherals.PORTB,
+        &pcc_lpspi1,
+        &pcc_portb,
+        &spc,
+        sbc::Config::default(),
+    )
+    .unwrap();
 
     // Configure the can i/o pins
     let porte = peripherals.PORTE;
@@ -71,6 +85,7 @@ fn main() -> ! {
                 }
                 if i & 1000 == 0 {
                     wdog.reset();
+                    sbc.kick().unwrap();
                 }
             }
         }
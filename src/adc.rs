@@ -0,0 +1,470 @@
+//! The SAR Analog to Digital Converters (ADC0 and ADC1)
+//!
+//! The ADC is clocked from its PCC functional clock, which must be between 2 and 50 MHz after
+//! the ADC's own divider.
+//!
+//! ```rust
+//! let pcc_adc0: pcc::Adc0 = pcc.enable_with_source(pcc::ClockSource::Sircdiv2).unwrap();
+//! let adc = adc::Adc::init(&p.ADC0, &pcc_adc0, &spc, adc::Config::default()).unwrap();
+//! let value = adc.convert(12).unwrap();
+//! ```
+
+#![allow(dead_code)]
+
+use s32k144;
+use s32k144::adc0;
+
+use crate::{pcc, spc};
+
+/// Number of times a status flag is checked before giving up
+const STATUS_CHECKS: u32 = 1_000_000;
+
+/// The highest ADC clock frequency
+const ADC_CLOCK_MAX: u32 = 50_000_000;
+
+/// The lowest ADC clock frequency
+const ADC_CLOCK_MIN: u32 = 2_000_000;
+
+/// Value of `ADCH` that disables the converter
+const CHANNEL_DISABLED: u8 = 0b11_1111;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The functional clock of the ADC is not running
+    NoClock,
+
+    /// The register block is not the ADC of the clock guard
+    WrongInstance,
+
+    /// The functional clock can't be divided to the allowed ADC clock range
+    ClockOutOfRange,
+
+    /// The calibration didn't finish in time
+    CalibrationTimeout,
+
+    /// The channel number is not an input of the ADC
+    InvalidChannel,
+
+    /// The conversion didn't complete in time
+    ConversionTimeout,
+
+    /// The configuration of the ADC can't be used, e.g. a hardware trigger for `Adc::convert`
+    InvalidConfig,
+}
+
+/// One of the two ADC instances, used for the SIM trigger selection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instance {
+    Adc0,
+    Adc1,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The PCC clock guards of the ADCs, `pcc::Adc0` and `pcc::Adc1`
+pub trait AdcClock: sealed::Sealed {
+    /// Return the register block of the ADC the guard belongs to
+    fn ptr() -> *const adc0::RegisterBlock;
+
+    /// Return the frequency of the functional clock if running
+    fn functional_clock_freq(&self, spc: &spc::Spc) -> Option<u32>;
+}
+
+macro_rules! adc_clock {
+    ($($Guard:ident: $ADC:ident,)+) => {
+        $(
+            impl<'a> sealed::Sealed for pcc::$Guard<'a> {}
+
+            impl<'a> AdcClock for pcc::$Guard<'a> {
+                fn ptr() -> *const adc0::RegisterBlock {
+                    s32k144::$ADC::ptr()
+                }
+
+                fn functional_clock_freq(&self, spc: &spc::Spc) -> Option<u32> {
+                    pcc::$Guard::functional_clock_freq(self, spc)
+                }
+            }
+        )+
+    };
+}
+
+adc_clock! {
+    Adc0: ADC0,
+    Adc1: ADC1,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Resolution {
+    Bits8,
+    Bits10,
+    Bits12,
+}
+
+impl Resolution {
+    /// Return the largest conversion result
+    pub fn max_value(self) -> u16 {
+        match self {
+            Resolution::Bits8 => 0xFF,
+            Resolution::Bits10 => 0x3FF,
+            Resolution::Bits12 => 0xFFF,
+        }
+    }
+
+    fn mode(self) -> u8 {
+        match self {
+            Resolution::Bits8 => 0b00,
+            Resolution::Bits10 => 0b10,
+            Resolution::Bits12 => 0b01,
+        }
+    }
+}
+
+/// Number of conversions averaged in hardware for each result
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Averaging {
+    Disabled,
+    Samples4,
+    Samples8,
+    Samples16,
+    Samples32,
+}
+
+/// What starts a conversion
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Trigger {
+    /// Writing the channel with `start` or `convert`
+    Software,
+
+    /// A hardware trigger, selected with `select_hardware_trigger`
+    Hardware,
+}
+
+/// Source of the hardware trigger of an ADC
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HardwareTrigger {
+    /// The Programmable Delay Block
+    Pdb,
+
+    /// The trigger multiplexer
+    Trgmux,
+}
+
+/// Compare function, conversions that don't match are discarded and don't set the complete flag
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compare {
+    Disabled,
+
+    /// Result is less than the value
+    LessThan(u16),
+
+    /// Result is greater than or equal to the value
+    GreaterOrEqual(u16),
+
+    /// Result is between `low` and `high`, inclusive
+    Inside {
+        low: u16,
+        high: u16,
+    },
+
+    /// Result is less than `low` or greater than `high`
+    Outside {
+        low: u16,
+        high: u16,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Config {
+    pub resolution: Resolution,
+    pub averaging: Averaging,
+    pub trigger: Trigger,
+
+    /// Start a new conversion as soon as the previous is complete
+    pub continuous: bool,
+
+    pub compare: Compare,
+
+    /// Generate an interrupt when a conversion is complete
+    pub interrupt: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            resolution: Resolution::Bits12,
+            averaging: Averaging::Disabled,
+            trigger: Trigger::Software,
+            continuous: false,
+            compare: Compare::Disabled,
+            interrupt: false,
+        }
+    }
+}
+
+/// Return the smallest ADIV setting bringing `source` into the ADC clock range
+fn find_clock_div(source: u32) -> Result<u8, Error> {
+    for adiv in 0..4 {
+        let freq = source >> adiv;
+        if freq < ADC_CLOCK_MIN {
+            break;
+        }
+        if freq <= ADC_CLOCK_MAX {
+            return Ok(adiv);
+        }
+    }
+    Err(Error::ClockOutOfRange)
+}
+
+pub struct Adc<'a> {
+    adc: &'a adc0::RegisterBlock,
+    config: Config,
+}
+
+impl<'a> Adc<'a> {
+    /// Configure and calibrate the ADC
+    ///
+    /// The ADC clock is divided from the functional clock of `pcc_adc`, which must be enabled with
+    /// a source. `adc` must be the ADC of the clock guard.
+    pub fn init<P: AdcClock>(
+        adc: &'a adc0::RegisterBlock,
+        pcc_adc: &'a P,
+        spc: &spc::Spc,
+        config: Config,
+    ) -> Result<Adc<'a>, Error> {
+        if adc as *const adc0::RegisterBlock != P::ptr() {
+            return Err(Error::WrongInstance);
+        }
+        let source_frequency = pcc_adc.functional_clock_freq(spc).ok_or(Error::NoClock)?;
+        let adiv = find_clock_div(source_frequency)?;
+
+        adc.sc1a
+            .write(|w| unsafe { w.adch().bits(CHANNEL_DISABLED) });
+        adc.cfg1.write(|w| unsafe {
+            w.adiclk()
+                .bits(0b00)
+                .adiv()
+                .bits(adiv)
+                .mode()
+                .bits(config.resolution.mode())
+        });
+
+        let adc = Adc {
+            adc: adc,
+            config: config,
+        };
+        adc.calibrate()?;
+
+        Ok(adc)
+    }
+
+    /// Run the calibration
+    ///
+    /// This is done by `init`, and only has to be repeated if the supply or temperature changes
+    /// a lot.
+    pub fn calibrate(&self) -> Result<(), Error> {
+        let adc = self.adc;
+
+        adc.sc2.modify(|_, w| w.adtrg().clear_bit());
+
+        adc.clps.write(|w| unsafe { w.bits(0) });
+        adc.clp3.write(|w| unsafe { w.bits(0) });
+        adc.clp2.write(|w| unsafe { w.bits(0) });
+        adc.clp1.write(|w| unsafe { w.bits(0) });
+        adc.clp0.write(|w| unsafe { w.bits(0) });
+        adc.clpx.write(|w| unsafe { w.bits(0) });
+        adc.clp9.write(|w| unsafe { w.bits(0) });
+
+        // Calibrate with the highest hardware averaging
+        adc.sc3
+            .write(|w| unsafe { w.cal().set_bit().avge().set_bit().avgs().bits(0b11) });
+
+        let mut checks = 0;
+        while adc.sc3.read().cal().bit_is_set() {
+            checks += 1;
+            if checks >= STATUS_CHECKS {
+                return Err(Error::CalibrationTimeout);
+            }
+        }
+
+        self.configure();
+        Ok(())
+    }
+
+    fn configure(&self) {
+        let config = self.config;
+        let (avge, avgs) = match config.averaging {
+            Averaging::Disabled => (false, 0b00),
+            Averaging::Samples4 => (true, 0b00),
+            Averaging::Samples8 => (true, 0b01),
+            Averaging::Samples16 => (true, 0b10),
+            Averaging::Samples32 => (true, 0b11),
+        };
+
+        self.adc.sc3.write(|w| unsafe {
+            w.adco()
+                .bit(config.continuous)
+                .avge()
+                .bit(avge)
+                .avgs()
+                .bits(avgs)
+        });
+
+        let (acfe, acfgt, acren, cv1, cv2) = match config.compare {
+            Compare::Disabled => (false, false, false, 0, 0),
+            Compare::LessThan(value) => (true, false, false, value, 0),
+            Compare::GreaterOrEqual(value) => (true, true, false, value, 0),
+            Compare::Inside { low, high } => (true, true, true, low, high),
+            Compare::Outside { low, high } => (true, false, true, low, high),
+        };
+
+        self.adc.cv1.write(|w| unsafe { w.cv().bits(cv1) });
+        self.adc.cv2.write(|w| unsafe { w.cv().bits(cv2) });
+        self.adc.sc2.modify(|_, w| {
+            w.adtrg()
+                .bit(config.trigger == Trigger::Hardware)
+                .acfe()
+                .bit(acfe)
+                .acfgt()
+                .bit(acfgt)
+                .acren()
+                .bit(acren)
+        });
+    }
+
+    /// Change the compare function
+    pub fn set_compare(&mut self, compare: Compare) {
+        self.config.compare = compare;
+        self.configure();
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Select the channel to convert
+    ///
+    /// With the software trigger this starts the conversion, with the hardware trigger the
+    /// conversion starts at the next trigger.
+    pub fn start(&self, channel: u8) -> Result<(), Error> {
+        if channel >= CHANNEL_DISABLED {
+            return Err(Error::InvalidChannel);
+        }
+
+        let interrupt = self.config.interrupt;
+        self.adc
+            .sc1a
+            .write(|w| unsafe { w.aien().bit(interrupt).adch().bits(channel) });
+        Ok(())
+    }
+
+    /// Stop the conversions
+    pub fn stop(&self) {
+        self.adc
+            .sc1a
+            .write(|w| unsafe { w.adch().bits(CHANNEL_DISABLED) });
+    }
+
+    /// Return true when a conversion is complete and the result can be read
+    pub fn is_complete(&self) -> bool {
+        self.adc.sc1a.read().coco().bit_is_set()
+    }
+
+    /// Return the result of the last conversion, this clears the complete flag
+    pub fn result(&self) -> u16 {
+        self.adc.ra.read().d().bits()
+    }
+
+    /// Convert a channel with the software trigger and wait for the result
+    ///
+    /// A conversion that doesn't match an enabled compare function never completes, and
+    /// `Error::ConversionTimeout` is returned. `Error::InvalidConfig` is returned with the hardware
+    /// trigger.
+    pub fn convert(&self, channel: u8) -> Result<u16, Error> {
+        if self.config.trigger != Trigger::Software {
+            return Err(Error::InvalidConfig);
+        }
+        self.start(channel)?;
+
+        let mut checks = 0;
+        while !self.is_complete() {
+            checks += 1;
+            if checks >= STATUS_CHECKS {
+                self.stop();
+                return Err(Error::ConversionTimeout);
+            }
+        }
+        Ok(self.result())
+    }
+
+    /// Return the largest conversion result with the configured resolution
+    pub fn max_value(&self) -> u16 {
+        self.config.resolution.max_value()
+    }
+}
+
+/// Select which hardware trigger starts conversions on an ADC
+pub fn select_hardware_trigger(
+    sim: &s32k144::sim::RegisterBlock,
+    instance: Instance,
+    trigger: HardwareTrigger,
+) {
+    let (trgsel, pretrgsel) = match trigger {
+        HardwareTrigger::Pdb => (false, 0b00),
+        HardwareTrigger::Trgmux => (true, 0b01),
+    };
+
+    sim.adcopt.modify(|_, w| unsafe {
+        match instance {
+            Instance::Adc0 => w.adc0trgsel().bit(trgsel).adc0pretrgsel().bits(pretrgsel),
+            Instance::Adc1 => w.adc1trgsel().bit(trgsel).adc1pretrgsel().bits(pretrgsel),
+        }
+    });
+}
+
+/// The potentiometer on the EVB, connected to PTC14 (ADC0_SE12)
+pub struct Potentiometer<'a> {
+    adc: &'a Adc<'a>,
+    pcc_portc: &'a pcc::PortC<'a>,
+}
+
+impl<'a> Potentiometer<'a> {
+    const CHANNEL: u8 = 12;
+
+    /// Mux PTC14 to the ADC
+    ///
+    /// `Error::InvalidConfig` is returned unless the ADC is ADC0, using the software trigger with
+    /// the compare function disabled.
+    pub fn init(
+        adc: &'a Adc<'a>,
+        portc: &'a s32k144::portc::RegisterBlock,
+        pcc_portc: &'a pcc::PortC,
+    ) -> Result<Self, Error> {
+        let is_adc0 = adc.adc as *const adc0::RegisterBlock == s32k144::ADC0::ptr();
+        if !is_adc0
+            || adc.config.trigger != Trigger::Software
+            || adc.config.compare != Compare::Disabled
+        {
+            return Err(Error::InvalidConfig);
+        }
+
+        portc.pcr14.modify(|_, w| w.mux().bits(0b000));
+
+        Ok(Potentiometer {
+            adc: adc,
+            pcc_portc: pcc_portc,
+        })
+    }
+
+    /// Return the raw conversion result
+    pub fn read(&self) -> Result<u16, Error> {
+        self.adc.convert(Self::CHANNEL)
+    }
+
+    /// Return the position of the potentiometer, from 0 to 1000
+    pub fn read_permille(&self) -> Result<u16, Error> {
+        let max = u32::from(self.adc.max_value());
+        Ok((u32::from(self.read()?) * 1000 / max) as u16)
+    }
+}
@@ -0,0 +1,243 @@
+//! Blink patterns for showing system state on the RGB LED
+//!
+//! A `Blinker` steps through a `Pattern` one tick at a time and repeats it forever. The durations
+//! are counted in ticks, and the patterns are made to look right with a tick of about 100 ms,
+//! e.g. from SysTick or LPIT.
+//!
+//! ```rust
+//! let led = led::RgbLed::init(&p.PTD, &p.PORTD, &pcc_portd);
+//! let mut blinker = blink::Blinker::new(blink::Pattern::Heartbeat(blink::Color::Green));
+//!
+//! // In the tick handler
+//! blinker.show(&led);
+//! ```
+
+use crate::led;
+
+/// The colors the RGB LED can show when each channel is either on or off
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Color {
+    Off,
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Cyan,
+    Magenta,
+    White,
+}
+
+impl Color {
+    /// Return which of the red, green and blue channels are on
+    pub fn channels(self) -> (bool, bool, bool) {
+        match self {
+            Color::Off => (false, false, false),
+            Color::Red => (true, false, false),
+            Color::Green => (false, true, false),
+            Color::Blue => (false, false, true),
+            Color::Yellow => (true, true, false),
+            Color::Cyan => (false, true, true),
+            Color::Magenta => (true, false, true),
+            Color::White => (true, true, true),
+        }
+    }
+}
+
+/// Colors shown by `Pattern::ColorCycle`
+const CYCLE: [Color; 6] = [
+    Color::Red,
+    Color::Yellow,
+    Color::Green,
+    Color::Cyan,
+    Color::Blue,
+    Color::Magenta,
+];
+
+/// A repeating blink pattern
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Pattern {
+    /// Constantly on
+    Solid(Color),
+
+    /// Two short blinks followed by a pause, once a second
+    Heartbeat(Color),
+
+    /// `count` short blinks followed by a pause, to show an error code
+    Blinks { color: Color, count: u8 },
+
+    /// SOS in morse code
+    Sos(Color),
+
+    /// Cycle through all colors except white
+    ColorCycle,
+}
+
+impl Pattern {
+    /// Return the color and duration in ticks of step `index`, or `None` after the last step
+    pub fn step(&self, index: usize) -> Option<(Color, u16)> {
+        match *self {
+            Pattern::Solid(color) => match index {
+                0 => Some((color, 1)),
+                _ => None,
+            },
+            Pattern::Heartbeat(color) => match index {
+                0 | 2 => Some((color, 1)),
+                1 => Some((Color::Off, 2)),
+                3 => Some((Color::Off, 6)),
+                _ => None,
+            },
+            Pattern::Blinks { color, count } => {
+                let blinks = 2 * usize::from(count);
+                if index < blinks {
+                    if index % 2 == 0 {
+                        Some((color, 2))
+                    } else {
+                        Some((Color::Off, 3))
+                    }
+                } else if index == blinks {
+                    Some((Color::Off, 10))
+                } else {
+                    None
+                }
+            }
+            Pattern::Sos(color) => {
+                // Three dots, three dashes and three dots, each followed by a gap
+                let symbol = index / 2;
+                if symbol >= 9 {
+                    None
+                } else if index % 2 == 0 {
+                    let dash = symbol >= 3 && symbol < 6;
+                    Some((color, if dash { 3 } else { 1 }))
+                } else {
+                    let gap = match symbol {
+                        8 => 7,
+                        2 | 5 => 3,
+                        _ => 1,
+                    };
+                    Some((Color::Off, gap))
+                }
+            }
+            Pattern::ColorCycle => CYCLE.get(index).map(|&color| (color, 5)),
+        }
+    }
+}
+
+/// Plays a pattern, one step at a time
+#[derive(Debug, Clone, Copy)]
+pub struct Blinker {
+    pattern: Pattern,
+    index: usize,
+    elapsed: u16,
+}
+
+impl Blinker {
+    pub fn new(pattern: Pattern) -> Self {
+        Blinker {
+            pattern: pattern,
+            index: 0,
+            elapsed: 0,
+        }
+    }
+
+    /// Start playing another pattern from the beginning
+    pub fn play(&mut self, pattern: Pattern) {
+        *self = Blinker::new(pattern);
+    }
+
+    pub fn pattern(&self) -> Pattern {
+        self.pattern
+    }
+
+    /// Advance the pattern by one tick and return the color to show until the next tick
+    pub fn tick(&mut self) -> Color {
+        loop {
+            match self.pattern.step(self.index) {
+                None if self.index == 0 => return Color::Off,
+                None => self.index = 0,
+                Some((_, duration)) if self.elapsed >= duration => {
+                    self.index += 1;
+                    self.elapsed = 0;
+                }
+                Some((color, _)) => {
+                    self.elapsed += 1;
+                    return color;
+                }
+            }
+        }
+    }
+
+    /// Advance the pattern by one tick and show it on the LED
+    pub fn show(&mut self, led: &led::RgbLed) {
+        let (red, green, blue) = self.tick().channels();
+        led.set(red, blue, green);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(blinker: &mut Blinker, n: usize) -> Vec<Color> {
+        (0..n).map(|_| blinker.tick()).collect()
+    }
+
+    #[test]
+    fn heartbeat_period() {
+        let mut blinker = Blinker::new(Pattern::Heartbeat(Color::Green));
+        let first = ticks(&mut blinker, 10);
+        let g = Color::Green;
+        let o = Color::Off;
+        assert_eq!(first, vec![g, o, o, g, o, o, o, o, o, o]);
+        assert_eq!(ticks(&mut blinker, 10), first);
+    }
+
+    #[test]
+    fn sos_timing() {
+        let mut blinker = Blinker::new(Pattern::Sos(Color::Red));
+        let period = ticks(&mut blinker, 34);
+        let r = Color::Red;
+        let o = Color::Off;
+
+        // Dots, a letter gap, and the first dash
+        assert_eq!(period[..12], [r, o, r, o, r, o, o, o, r, r, r, o]);
+        assert_eq!(period.iter().filter(|&&c| c == r).count(), 15);
+        // The word gap at the end
+        assert_eq!(period[27..], [o; 7]);
+        assert_eq!(ticks(&mut blinker, 34), period);
+    }
+
+    #[test]
+    fn zero_blinks_is_off() {
+        let mut blinker = Blinker::new(Pattern::Blinks {
+            color: Color::Blue,
+            count: 0,
+        });
+        assert!(ticks(&mut blinker, 25).iter().all(|&c| c == Color::Off));
+    }
+
+    #[test]
+    fn blinks_repeat() {
+        let mut blinker = Blinker::new(Pattern::Blinks {
+            color: Color::Blue,
+            count: 2,
+        });
+        let b = Color::Blue;
+        let o = Color::Off;
+        let period = ticks(&mut blinker, 20);
+        assert_eq!(period[..10], [b, b, o, o, o, b, b, o, o, o]);
+        assert_eq!(period[10..], [o; 10]);
+        assert_eq!(blinker.tick(), b);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut blinker = Blinker::new(Pattern::ColorCycle);
+        let period = ticks(&mut blinker, 30);
+        assert_eq!(period[0], Color::Red);
+        assert_eq!(period[29], Color::Magenta);
+        assert_eq!(blinker.tick(), Color::Red);
+
+        let mut blinker = Blinker::new(Pattern::Solid(Color::White));
+        assert!(ticks(&mut blinker, 5).iter().all(|&c| c == Color::White));
+    }
+}
@@ -0,0 +1,315 @@
+//! The SW2 and SW3 push buttons on the EVB
+//!
+//! The buttons are connected to PTC12 (SW2) and PTC13 (SW3) and read high while pressed.
+//! They are debounced by sampling them from a periodic tick, or from the PORTC interrupt.
+//!
+//! ```rust
+//! let gpioc = gpio::GpioC::split(&p.PORTC, &p.PTC, &pcc_portc);
+//! let mut buttons = button::Buttons::init(gpioc.ptc12, gpioc.ptc13, button::Config::default());
+//! ```
+
+#![allow(dead_code)]
+
+use embedded_hal::digital::v2::InputPin;
+
+use crate::gpio::{Floating, Input, PinInterrupt, PTC12, PTC13};
+
+/// One of the user push buttons
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Button {
+    /// SW2 on PTC12
+    Sw2,
+
+    /// SW3 on PTC13
+    Sw3,
+}
+
+/// A change of the debounced button state
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Event {
+    /// The button was pressed
+    Pressed,
+
+    /// The button was released
+    Released,
+
+    /// The button has been held down for the long press time, reported once per press
+    LongPress,
+}
+
+/// The events of both buttons from one sample
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ButtonEvents {
+    pub sw2: Option<Event>,
+    pub sw3: Option<Event>,
+}
+
+/// Debouncing of a button from periodic samples
+///
+/// This holds no hardware, so it can be fed with simulated samples.
+#[derive(Debug, Clone, Copy)]
+pub struct Debouncer {
+    debounce_samples: u8,
+    long_press_samples: Option<u16>,
+    pressed: bool,
+    changed_samples: u8,
+    held_samples: u16,
+    long_press_reported: bool,
+}
+
+impl Debouncer {
+    /// Create a debouncer for a released button
+    ///
+    /// The state changes after `debounce_samples` equal samples in a row, and a long press is
+    /// reported after the button has been held for `long_press_samples` samples.
+    pub const fn new(debounce_samples: u8, long_press_samples: Option<u16>) -> Self {
+        Debouncer {
+            debounce_samples: debounce_samples,
+            long_press_samples: long_press_samples,
+            pressed: false,
+            changed_samples: 0,
+            held_samples: 0,
+            long_press_reported: false,
+        }
+    }
+
+    /// Return the debounced state of the button
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Feed a sample of the button, true if it reads as pressed
+    pub fn update(&mut self, sample: bool) -> Option<Event> {
+        if sample == self.pressed {
+            self.changed_samples = 0;
+            return self.update_held();
+        }
+
+        self.changed_samples = self.changed_samples.saturating_add(1);
+        if self.changed_samples < self.debounce_samples {
+            return None;
+        }
+
+        self.pressed = sample;
+        self.changed_samples = 0;
+        self.held_samples = 0;
+        self.long_press_reported = false;
+
+        if sample {
+            Some(Event::Pressed)
+        } else {
+            Some(Event::Released)
+        }
+    }
+
+    fn update_held(&mut self) -> Option<Event> {
+        if !self.pressed || self.long_press_reported {
+            return None;
+        }
+
+        let long_press_samples = self.long_press_samples?;
+        self.held_samples = self.held_samples.saturating_add(1);
+        if self.held_samples >= long_press_samples {
+            self.long_press_reported = true;
+            Some(Event::LongPress)
+        } else {
+            None
+        }
+    }
+}
+
+/// Button configuration, times are counted in samples
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// Number of equal samples in a row before a press or release is accepted
+    pub debounce_samples: u8,
+
+    /// Number of samples the button is held before a long press is reported, `None` to disable
+    pub long_press_samples: Option<u16>,
+
+    /// Enable the PORTC digital filter on the button pins
+    ///
+    /// The filter clock and width are common for PORTC, and must be configured with
+    /// `gpio::ControlC::set_digital_filter` before, e.g. the LPO with `gpio::FILTER_WIDTH_MAX`.
+    /// This is useful when the buttons are only sampled on interrupts.
+    pub digital_filter: bool,
+}
+
+impl Default for Config {
+    /// Suitable for a 10 ms tick: 50 ms debounce and 1 s long press
+    fn default() -> Self {
+        Config {
+            debounce_samples: 5,
+            long_press_samples: Some(100),
+            digital_filter: false,
+        }
+    }
+}
+
+pub struct Buttons<'a> {
+    sw2_pin: PTC12<'a, Input<Floating>>,
+    sw3_pin: PTC13<'a, Input<Floating>>,
+    sw2: Debouncer,
+    sw3: Debouncer,
+}
+
+impl<'a> Buttons<'a> {
+    /// Configure the button pins as inputs
+    pub fn init<SW2, SW3>(
+        sw2_pin: PTC12<'a, SW2>,
+        sw3_pin: PTC13<'a, SW3>,
+        config: Config,
+    ) -> Self {
+        // The buttons have external pull down resistors
+        let mut sw2_pin = sw2_pin.into_floating_input();
+        let mut sw3_pin = sw3_pin.into_floating_input();
+        sw2_pin.set_passive_filter(true);
+        sw3_pin.set_passive_filter(true);
+        sw2_pin.set_digital_filter(config.digital_filter);
+        sw3_pin.set_digital_filter(config.digital_filter);
+
+        let debouncer = Debouncer::new(config.debounce_samples, config.long_press_samples);
+
+        Buttons {
+            sw2_pin: sw2_pin,
+            sw3_pin: sw3_pin,
+            sw2: debouncer,
+            sw3: debouncer,
+        }
+    }
+
+    /// Return the debounced state of the button
+    pub fn is_pressed(&self, button: Button) -> bool {
+        match button {
+            Button::Sw2 => self.sw2.is_pressed(),
+            Button::Sw3 => self.sw3.is_pressed(),
+        }
+    }
+
+    /// Return the state of the pin without debouncing
+    pub fn is_pressed_raw(&self, button: Button) -> bool {
+        let high = match button {
+            Button::Sw2 => self.sw2_pin.is_high(),
+            Button::Sw3 => self.sw3_pin.is_high(),
+        };
+        high.unwrap()
+    }
+
+    /// Sample both buttons, call this from a periodic tick
+    pub fn tick(&mut self) -> ButtonEvents {
+        ButtonEvents {
+            sw2: self.sw2.update(self.is_pressed_raw(Button::Sw2)),
+            sw3: self.sw3.update(self.is_pressed_raw(Button::Sw3)),
+        }
+    }
+
+    /// Enable or disable the PORTC interrupt on both edges of the button pins
+    ///
+    /// The `PORTC` interrupt must also be unmasked in the NVIC.
+    pub fn set_interrupt(&mut self, enable: bool) {
+        let interrupt = if enable {
+            PinInterrupt::Either
+        } else {
+            PinInterrupt::Disabled
+        };
+        self.sw2_pin.clear_interrupt_pending();
+        self.sw3_pin.clear_interrupt_pending();
+        self.sw2_pin.set_interrupt(interrupt);
+        self.sw3_pin.set_interrupt(interrupt);
+    }
+
+    /// Clear the interrupt flags of the buttons and sample the buttons that caused the interrupt
+    ///
+    /// Call this from the `PORTC` interrupt handler. Each interrupt counts as one sample, so use
+    /// `debounce_samples` of 1 together with the digital filter when only sampling on
+    /// interrupts. Long presses are only detected by `tick`.
+    pub fn on_interrupt(&mut self) -> ButtonEvents {
+        let mut events = ButtonEvents::default();
+        if self.sw2_pin.is_interrupt_pending() {
+            self.sw2_pin.clear_interrupt_pending();
+            events.sw2 = self.sw2.update(self.is_pressed_raw(Button::Sw2));
+        }
+        if self.sw3_pin.is_interrupt_pending() {
+            self.sw3_pin.clear_interrupt_pending();
+            events.sw3 = self.sw3.update(self.is_pressed_raw(Button::Sw3));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed the samples and return the events
+    fn feed(debouncer: &mut Debouncer, samples: &[bool]) -> Vec<Option<Event>> {
+        samples.iter().map(|&s| debouncer.update(s)).collect()
+    }
+
+    #[test]
+    fn bounces_are_rejected() {
+        let mut debouncer = Debouncer::new(3, None);
+        let events = feed(
+            &mut debouncer,
+            &[true, false, true, true, false, false, true, false],
+        );
+        assert!(events.iter().all(Option::is_none));
+        assert!(!debouncer.is_pressed());
+    }
+
+    #[test]
+    fn press_and_release() {
+        let mut debouncer = Debouncer::new(3, None);
+        assert_eq!(
+            feed(&mut debouncer, &[true, true, true]),
+            vec![None, None, Some(Event::Pressed)]
+        );
+        assert!(debouncer.is_pressed());
+
+        // A short glitch while pressed is ignored
+        assert_eq!(
+            feed(&mut debouncer, &[false, true, true]),
+            vec![None, None, None]
+        );
+
+        assert_eq!(
+            feed(&mut debouncer, &[false, false, false]),
+            vec![None, None, Some(Event::Released)]
+        );
+        assert!(!debouncer.is_pressed());
+    }
+
+    #[test]
+    fn one_long_press_per_hold() {
+        let mut debouncer = Debouncer::new(1, Some(3));
+        assert_eq!(debouncer.update(true), Some(Event::Pressed));
+
+        let events = feed(&mut debouncer, &[true; 10]);
+        assert_eq!(events[2], Some(Event::LongPress));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|&&e| e == Some(Event::LongPress))
+                .count(),
+            1
+        );
+
+        // A new press reports a new long press
+        assert_eq!(debouncer.update(false), Some(Event::Released));
+        assert_eq!(debouncer.update(true), Some(Event::Pressed));
+        assert_eq!(
+            feed(&mut debouncer, &[true; 3]),
+            vec![None, None, Some(Event::LongPress)]
+        );
+    }
+
+    #[test]
+    fn no_long_press_when_disabled() {
+        let mut debouncer = Debouncer::new(1, None);
+        assert_eq!(debouncer.update(true), Some(Event::Pressed));
+        assert!(feed(&mut debouncer, &[true; 1000])
+            .iter()
+            .all(Option::is_none));
+        assert!(debouncer.is_pressed());
+    }
+}
@@ -49,12 +49,16 @@ pub struct LpuartConsole<'a> {
 }
 
 impl<'a> LpuartConsole<'a> {
+    /// Initialize the console at 115200 baud
+    ///
+    /// The LPUART must be clocked from soscdiv2, which must be enabled in `spc`.
     pub fn init(lpuart: &'a s32k144::lpuart0::RegisterBlock, spc: &'a spc::Spc<'a>) -> Self {
         let mut uart_config = lpuart::Config::default();
         uart_config.baudrate = 115200;
 
         LpuartConsole {
-            lpuart: lpuart::Lpuart::init(lpuart, spc, uart_config, 8_000_000).unwrap(),
+            lpuart: lpuart::Lpuart::init(lpuart, spc, uart_config, spc.soscdiv2_freq().unwrap())
+                .unwrap(),
         }
     }
 }
@@ -0,0 +1,866 @@
+//! The FlexTimer Modules (FTM0 to FTM3)
+//!
+//! The counter is clocked from the FTM's PCC functional clock, and the prescaler and modulo are
+//! computed from its frequency. All four instances have the same registers.
+//!
+//! FTM1 and FTM2 can also decode quadrature encoder signals with `QuadratureDecoder`.
+//!
+//! ```rust
+//! let pcc_ftm0: pcc::Ftm0 = pcc.enable_with_source(pcc::Div1ClockSource::Soscdiv1).unwrap();
+//! let mut ftm = ftm::Ftm::init(&p.FTM0, &pcc_ftm0, &spc).unwrap();
+//! ftm.start_pwm(20_000, ftm::Alignment::Center).unwrap();
+//!
+//! let mut high_side = ftm.pwm_channel(ftm::Channel::Ch0);
+//! let mut low_side = ftm.pwm_channel(ftm::Channel::Ch1);
+//! ftm.set_complementary(ftm::Pair::Pair0, 500).unwrap();
+//!
+//! high_side.set_duty(high_side.get_max_duty() / 2);
+//! high_side.enable();
+//! low_side.enable();
+//! ```
+
+#![allow(dead_code)]
+
+use embedded_hal::PwmPin;
+use s32k144::{self, ftm0};
+
+use crate::{pcc, spc};
+
+/// Channel status and control bits
+const CNSC_CHF: u32 = 1 << 7;
+const CNSC_CHIE: u32 = 1 << 6;
+const CNSC_MSB: u32 = 1 << 5;
+const CNSC_MSA: u32 = 1 << 4;
+const CNSC_ELSB: u32 = 1 << 3;
+const CNSC_ELSA: u32 = 1 << 2;
+
+/// Bits of a channel pair in COMBINE, shifted by 8 for each pair
+const COMBINE_COMP: u32 = 1 << 1;
+const COMBINE_DTEN: u32 = 1 << 4;
+const COMBINE_PAIR_MASK: u32 = 0xFF;
+
+/// Highest deadtime count in DEADTIME.DTVAL
+const DEADTIME_MAX: u32 = 63;
+
+/// Highest value of an input filter
+const FILTER_MAX: u8 = 15;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The functional clock of the FTM is not running
+    NoClock,
+
+    /// The register block is not the FTM of the clock guard
+    WrongInstance,
+
+    /// The PWM frequency can't be reached from the functional clock
+    FrequencyOutOfRange,
+
+    /// The deadtime is too long for the functional clock
+    DeadtimeOutOfRange,
+
+    /// Input filters are only available on channel 0 to 3, with values up to 15
+    InvalidFilter,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Channel {
+    Ch0,
+    Ch1,
+    Ch2,
+    Ch3,
+    Ch4,
+    Ch5,
+    Ch6,
+    Ch7,
+}
+
+impl Channel {
+    fn index(self) -> u32 {
+        self as u32
+    }
+}
+
+/// A pair of channels, used for complementary outputs
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Pair {
+    /// Channel 0 and 1
+    Pair0,
+
+    /// Channel 2 and 3
+    Pair1,
+
+    /// Channel 4 and 5
+    Pair2,
+
+    /// Channel 6 and 7
+    Pair3,
+}
+
+impl Pair {
+    fn channels(self) -> (Channel, Channel) {
+        match self {
+            Pair::Pair0 => (Channel::Ch0, Channel::Ch1),
+            Pair::Pair1 => (Channel::Ch2, Channel::Ch3),
+            Pair::Pair2 => (Channel::Ch4, Channel::Ch5),
+            Pair::Pair3 => (Channel::Ch6, Channel::Ch7),
+        }
+    }
+}
+
+/// PWM alignment, common for all channels of an FTM
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Alignment {
+    /// The counter counts up and restarts, the output changes at the match
+    Edge,
+
+    /// The counter counts up and down, the pulse is centered in the period
+    Center,
+}
+
+/// Edge captured in input capture mode
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+/// Level of a PWM output during the pulse
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Polarity {
+    /// The output is high during the pulse
+    HighTrue,
+
+    /// The output is low during the pulse, e.g. for a LED connected to the supply
+    LowTrue,
+}
+
+/// What happens to the output at an output compare match
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CompareAction {
+    Toggle,
+    Clear,
+    Set,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The PCC clock guards of the FTMs, `pcc::Ftm0` to `pcc::Ftm3`
+pub trait FtmClock: sealed::Sealed {
+    /// Return the register block of the FTM the guard belongs to
+    fn ptr() -> *const ftm0::RegisterBlock;
+
+    /// Return the frequency of the functional clock if running
+    fn functional_clock_freq(&self, spc: &spc::Spc) -> Option<u32>;
+}
+
+/// The clock guards of the FTMs with a quadrature decoder, `pcc::Ftm1` and `pcc::Ftm2`
+pub trait QuadratureTimer: FtmClock {}
+
+macro_rules! ftm_clock {
+    ($($Guard:ident: $FTM:ident,)+) => {
+        $(
+            impl<'a> sealed::Sealed for pcc::$Guard<'a> {}
+
+            impl<'a> FtmClock for pcc::$Guard<'a> {
+                fn ptr() -> *const ftm0::RegisterBlock {
+                    s32k144::$FTM::ptr()
+                }
+
+                fn functional_clock_freq(&self, spc: &spc::Spc) -> Option<u32> {
+                    pcc::$Guard::functional_clock_freq(self, spc)
+                }
+            }
+        )+
+    };
+}
+
+ftm_clock! {
+    Ftm0: FTM0,
+    Ftm1: FTM1,
+    Ftm2: FTM2,
+    Ftm3: FTM3,
+}
+
+impl<'a> QuadratureTimer for pcc::Ftm1<'a> {}
+impl<'a> QuadratureTimer for pcc::Ftm2<'a> {}
+
+/// Return an error unless `ftm` is the FTM of the clock guard `P`
+fn check_timer<P: FtmClock>(ftm: &ftm0::RegisterBlock) -> Result<(), Error> {
+    if ftm as *const ftm0::RegisterBlock == P::ptr() {
+        Ok(())
+    } else {
+        Err(Error::WrongInstance)
+    }
+}
+
+/// Return the prescaler and modulo giving a PWM period closest to `frequency`
+///
+/// The smallest prescaler is used, for the best duty cycle resolution. With edge alignment the
+/// modulo is kept below 0xFFFF so the duty cycle can reach 100%, and with center alignment it's
+/// kept at or below 0x7FFF, the highest modulo allowed in up-down counting.
+pub fn find_prescaler(
+    source: u32,
+    frequency: u32,
+    alignment: Alignment,
+) -> Result<(u8, u16), Error> {
+    let period = match alignment {
+        Alignment::Edge => frequency,
+        Alignment::Center => frequency.checked_mul(2).ok_or(Error::FrequencyOutOfRange)?,
+    };
+    if period == 0 {
+        return Err(Error::FrequencyOutOfRange);
+    }
+    let ticks_max = match alignment {
+        Alignment::Edge => 0xFFFF,
+        Alignment::Center => 0x7FFF,
+    };
+
+    for prescaler in 0..8 {
+        let ticks = (source >> prescaler) / period;
+        if ticks < 2 {
+            break;
+        }
+        if ticks <= ticks_max {
+            let modulo = match alignment {
+                Alignment::Edge => ticks - 1,
+                Alignment::Center => ticks,
+            };
+            return Ok((prescaler, modulo as u16));
+        }
+    }
+    Err(Error::FrequencyOutOfRange)
+}
+
+/// Return the DEADTIME prescaler setting and count for `deadtime_ns` nanoseconds
+pub fn find_deadtime(source: u32, deadtime_ns: u32) -> Result<(u8, u8), Error> {
+    let ticks = u64::from(source) * u64::from(deadtime_ns) / 1_000_000_000;
+
+    // DTPS divides by 1, 4 or 16
+    for &(dtps, div) in [(0b00, 1), (0b10, 4), (0b11, 16)].iter() {
+        let count = (ticks + div - 1) / div;
+        if count <= u64::from(DEADTIME_MAX) {
+            return Ok((dtps, count as u8));
+        }
+    }
+    Err(Error::DeadtimeOutOfRange)
+}
+
+/// Run `$body` with `$reg` bound to the register of `$channel`
+macro_rules! with_channel_reg {
+    ($ftm:expr, $channel:expr, [$($ch:ident => $r:ident),+], |$reg:ident| $body:expr) => {
+        match $channel {
+            $(
+                Channel::$ch => {
+                    let $reg = &$ftm.$r;
+                    $body
+                }
+            )+
+        }
+    };
+}
+
+fn write_cnsc(ftm: &ftm0::RegisterBlock, channel: Channel, value: u32) {
+    with_channel_reg!(ftm, channel, [
+        Ch0 => c0sc, Ch1 => c1sc, Ch2 => c2sc, Ch3 => c3sc,
+        Ch4 => c4sc, Ch5 => c5sc, Ch6 => c6sc, Ch7 => c7sc
+    ], |cnsc| cnsc.write(|w| unsafe { w.bits(value) }))
+}
+
+fn read_cnsc(ftm: &ftm0::RegisterBlock, channel: Channel) -> u32 {
+    with_channel_reg!(ftm, channel, [
+        Ch0 => c0sc, Ch1 => c1sc, Ch2 => c2sc, Ch3 => c3sc,
+        Ch4 => c4sc, Ch5 => c5sc, Ch6 => c6sc, Ch7 => c7sc
+    ], |cnsc| cnsc.read().bits())
+}
+
+fn write_cnv(ftm: &ftm0::RegisterBlock, channel: Channel, value: u16) {
+    with_channel_reg!(ftm, channel, [
+        Ch0 => c0v, Ch1 => c1v, Ch2 => c2v, Ch3 => c3v,
+        Ch4 => c4v, Ch5 => c5v, Ch6 => c6v, Ch7 => c7v
+    ], |cnv| cnv.write(|w| unsafe { w.bits(u32::from(value)) }))
+}
+
+fn read_cnv(ftm: &ftm0::RegisterBlock, channel: Channel) -> u16 {
+    with_channel_reg!(ftm, channel, [
+        Ch0 => c0v, Ch1 => c1v, Ch2 => c2v, Ch3 => c3v,
+        Ch4 => c4v, Ch5 => c5v, Ch6 => c6v, Ch7 => c7v
+    ], |cnv| cnv.read().bits() as u16)
+}
+
+/// Enable or disable the output of a channel with SC.PWMENn
+fn set_output_enable(ftm: &ftm0::RegisterBlock, channel: Channel, enable: bool) {
+    let mask = 1 << (16 + channel.index());
+    ftm.sc.modify(|r, w| unsafe {
+        if enable {
+            w.bits(r.bits() | mask)
+        } else {
+            w.bits(r.bits() & !mask)
+        }
+    });
+}
+
+pub struct Ftm<'a> {
+    ftm: &'a ftm0::RegisterBlock,
+    source_frequency: u32,
+    prescaler: u8,
+    modulo: u16,
+    alignment: Alignment,
+}
+
+impl<'a> Ftm<'a> {
+    /// Stop the counter and disable the write protection
+    ///
+    /// The counter is clocked from the functional clock of `pcc_ftm`, which must be enabled with
+    /// a source. `ftm` must be the FTM of the clock guard.
+    pub fn init<P: FtmClock>(
+        ftm: &'a ftm0::RegisterBlock,
+        pcc_ftm: &'a P,
+        spc: &spc::Spc,
+    ) -> Result<Self, Error> {
+        check_timer::<P>(ftm)?;
+        let source_frequency = pcc_ftm.functional_clock_freq(spc).ok_or(Error::NoClock)?;
+
+        ftm.sc.write(|w| unsafe { w.bits(0) });
+        ftm.mode.modify(|_, w| w.wpdis().set_bit());
+        ftm.cntin.write(|w| unsafe { w.bits(0) });
+        ftm.cnt.write(|w| unsafe { w.bits(0) });
+        ftm.combine.write(|w| unsafe { w.bits(0) });
+
+        Ok(Ftm {
+            ftm: ftm,
+            source_frequency: source_frequency,
+            prescaler: 0,
+            modulo: 0xFFFF,
+            alignment: Alignment::Edge,
+        })
+    }
+
+    /// Start the counter with a period of `frequency` Hz, for PWM and output compare
+    pub fn start_pwm(&mut self, frequency: u32, alignment: Alignment) -> Result<(), Error> {
+        let (prescaler, modulo) = find_prescaler(self.source_frequency, frequency, alignment)?;
+        self.prescaler = prescaler;
+        self.modulo = modulo;
+        self.alignment = alignment;
+        self.start_counter();
+        Ok(())
+    }
+
+    /// Start the counter free running over the full 16 bits, for input capture
+    pub fn start_free_running(&mut self, prescaler: u8) {
+        self.prescaler = prescaler & 0b111;
+        self.modulo = 0xFFFF;
+        self.alignment = Alignment::Edge;
+        self.start_counter();
+    }
+
+    fn start_counter(&self) {
+        let ftm = self.ftm;
+        let prescaler = self.prescaler;
+        let modulo = self.modulo;
+        let center = self.alignment == Alignment::Center;
+
+        ftm.sc.modify(|_, w| unsafe { w.clks().bits(0b00) });
+        ftm.cnt.write(|w| unsafe { w.bits(0) });
+        ftm.mod_.write(|w| unsafe { w.bits(u32::from(modulo)) });
+        ftm.sc
+            .modify(|_, w| unsafe { w.ps().bits(prescaler).cpwms().bit(center).clks().bits(0b11) });
+    }
+
+    /// Stop the counter
+    pub fn stop(&self) {
+        self.ftm.sc.modify(|_, w| unsafe { w.clks().bits(0b00) });
+    }
+
+    /// Return the frequency the counter is clocked with, after the prescaler
+    pub fn counter_freq(&self) -> u32 {
+        self.source_frequency >> self.prescaler
+    }
+
+    pub fn counter(&self) -> u16 {
+        self.ftm.cnt.read().bits() as u16
+    }
+
+    pub fn modulo(&self) -> u16 {
+        self.modulo
+    }
+
+    /// Return a PWM handle to a channel
+    ///
+    /// The channel is set up for PWM with high true pulses, and its output is disabled until
+    /// `PwmPin::enable` is called.
+    pub fn pwm_channel(&self, channel: Channel) -> PwmChannel<'a> {
+        set_output_enable(self.ftm, channel, false);
+        write_cnv(self.ftm, channel, 0);
+        write_cnsc(self.ftm, channel, CNSC_MSB | CNSC_ELSB);
+
+        let max_duty = match self.alignment {
+            Alignment::Edge => self.modulo.saturating_add(1),
+            Alignment::Center => self.modulo,
+        };
+
+        PwmChannel {
+            ftm: self.ftm,
+            channel: channel,
+            max_duty: max_duty,
+        }
+    }
+
+    /// Make the odd channel of a pair output the complement of the even channel, with deadtime
+    ///
+    /// The deadtime is common for all pairs. A deadtime of 0 disables deadtime insertion.
+    pub fn set_complementary(&self, pair: Pair, deadtime_ns: u32) -> Result<(), Error> {
+        let (dtps, dtval) = find_deadtime(self.source_frequency, deadtime_ns)?;
+        let shift = 8 * pair as u32;
+        let bits = if deadtime_ns > 0 {
+            COMBINE_COMP | COMBINE_DTEN
+        } else {
+            COMBINE_COMP
+        };
+
+        if deadtime_ns > 0 {
+            self.ftm
+                .deadtime
+                .write(|w| unsafe { w.bits((u32::from(dtps) << 6) | u32::from(dtval)) });
+        }
+        self.ftm.combine.modify(|r, w| unsafe {
+            w.bits((r.bits() & !(COMBINE_PAIR_MASK << shift)) | (bits << shift))
+        });
+
+        let (even, odd) = pair.channels();
+        write_cnsc(self.ftm, odd, read_cnsc(self.ftm, even) & !CNSC_CHF);
+        Ok(())
+    }
+
+    /// Make the channels of a pair independent again
+    pub fn clear_complementary(&self, pair: Pair) {
+        let shift = 8 * pair as u32;
+        self.ftm
+            .combine
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(COMBINE_PAIR_MASK << shift)) });
+    }
+
+    /// Configure a channel for input capture
+    ///
+    /// The counter value is captured on the selected edge. `filter` ignores pulses shorter than
+    /// `4 * filter` cycles of the functional clock, and is only available on channel 0 to 3.
+    pub fn configure_input_capture(
+        &self,
+        channel: Channel,
+        edge: Edge,
+        filter: u8,
+        interrupt: bool,
+    ) -> Result<(), Error> {
+        if filter > FILTER_MAX || (filter > 0 && channel.index() > 3) {
+            return Err(Error::InvalidFilter);
+        }
+
+        if channel.index() <= 3 {
+            let shift = 4 * channel.index();
+            self.ftm.filter.modify(|r, w| unsafe {
+                w.bits((r.bits() & !(0xF << shift)) | (u32::from(filter) << shift))
+            });
+        }
+
+        let els = match edge {
+            Edge::Rising => CNSC_ELSA,
+            Edge::Falling => CNSC_ELSB,
+            Edge::Both => CNSC_ELSA | CNSC_ELSB,
+        };
+        let chie = if interrupt { CNSC_CHIE } else { 0 };
+        set_output_enable(self.ftm, channel, false);
+        write_cnsc(self.ftm, channel, els | chie);
+        Ok(())
+    }
+
+    /// Configure a channel for output compare, the output changes when the counter equals `value`
+    pub fn configure_output_compare(
+        &self,
+        channel: Channel,
+        action: CompareAction,
+        value: u16,
+        interrupt: bool,
+    ) {
+        let els = match action {
+            CompareAction::Toggle => CNSC_ELSA,
+            CompareAction::Clear => CNSC_ELSB,
+            CompareAction::Set => CNSC_ELSA | CNSC_ELSB,
+        };
+        let chie = if interrupt { CNSC_CHIE } else { 0 };
+        write_cnv(self.ftm, channel, value);
+        write_cnsc(self.ftm, channel, CNSC_MSA | els | chie);
+        set_output_enable(self.ftm, channel, true);
+    }
+
+    /// Change the compare value of a channel
+    pub fn set_compare_value(&self, channel: Channel, value: u16) {
+        write_cnv(self.ftm, channel, value);
+    }
+
+    /// Return the counter value of the last capture
+    pub fn capture_value(&self, channel: Channel) -> u16 {
+        read_cnv(self.ftm, channel)
+    }
+
+    /// Return true if the channel has captured or matched since the flag was cleared
+    pub fn channel_event(&self, channel: Channel) -> bool {
+        read_cnsc(self.ftm, channel) & CNSC_CHF != 0
+    }
+
+    /// Clear the channel flag, this also clears the interrupt request
+    pub fn clear_channel_event(&self, channel: Channel) {
+        // The flag is cleared by writing 0 after reading it as 1
+        let value = read_cnsc(self.ftm, channel);
+        write_cnsc(self.ftm, channel, value & !CNSC_CHF);
+    }
+
+    /// Enable or disable the overflow interrupt
+    pub fn set_overflow_interrupt(&self, enable: bool) {
+        self.ftm.sc.modify(|_, w| w.toie().bit(enable));
+    }
+
+    /// Return true if the counter has overflowed since the flag was cleared
+    pub fn has_overflowed(&self) -> bool {
+        self.ftm.sc.read().tof().bit_is_set()
+    }
+
+    /// Clear the overflow flag, this also clears the interrupt request
+    pub fn clear_overflow(&self) {
+        // The flag is cleared by writing 0 after reading it as 1
+        self.ftm.sc.modify(|_, w| w.tof().clear_bit());
+    }
+}
+
+/// A PWM output channel of an FTM
+pub struct PwmChannel<'a> {
+    ftm: &'a ftm0::RegisterBlock,
+    channel: Channel,
+    max_duty: u16,
+}
+
+impl<'a> PwmChannel<'a> {
+    /// Select the output level during the pulse, channels start out high true
+    pub fn set_polarity(&mut self, polarity: Polarity) {
+        let els = match polarity {
+            Polarity::HighTrue => CNSC_ELSB,
+            Polarity::LowTrue => CNSC_ELSA,
+        };
+        write_cnsc(self.ftm, self.channel, CNSC_MSB | els);
+    }
+}
+
+impl<'a> PwmPin for PwmChannel<'a> {
+    type Duty = u16;
+
+    fn disable(&mut self) {
+        set_output_enable(self.ftm, self.channel, false);
+    }
+
+    fn enable(&mut self) {
+        set_output_enable(self.ftm, self.channel, true);
+    }
+
+    fn get_duty(&self) -> u16 {
+        read_cnv(self.ftm, self.channel)
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        self.max_duty
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        write_cnv(self.ftm, self.channel, duty);
+    }
+}
+
+/// Encoding of the quadrature decoder inputs, phase A is on channel 0 and phase B on channel 1
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum QuadratureMode {
+    /// Two signals in quadrature, counting on every edge
+    PhaseAB,
+
+    /// Phase A counts, phase B gives the direction
+    CountDirection,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct QuadratureConfig {
+    pub mode: QuadratureMode,
+    pub invert_a: bool,
+    pub invert_b: bool,
+
+    /// Input filter of phase A, see `Ftm::configure_input_capture`
+    pub filter_a: u8,
+
+    /// Input filter of phase B
+    pub filter_b: u8,
+}
+
+impl Default for QuadratureConfig {
+    fn default() -> Self {
+        QuadratureConfig {
+            mode: QuadratureMode::PhaseAB,
+            invert_a: false,
+            invert_b: false,
+            filter_a: 0,
+            filter_b: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// Signed position from a wrapping 16-bit counter
+///
+/// The counter must be sampled at least once every 32767 counts for the position to be correct.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PositionTracker {
+    last: u16,
+    position: i64,
+    delta: i16,
+}
+
+impl PositionTracker {
+    /// Start tracking from the counter value `raw`, at position 0
+    pub fn new(raw: u16) -> Self {
+        PositionTracker {
+            last: raw,
+            position: 0,
+            delta: 0,
+        }
+    }
+
+    /// Feed a new counter value and return the change since the last one
+    pub fn update(&mut self, raw: u16) -> i16 {
+        self.delta = raw.wrapping_sub(self.last) as i16;
+        self.last = raw;
+        self.position += i64::from(self.delta);
+        self.delta
+    }
+
+    pub fn position(&self) -> i64 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: i64) {
+        self.position = position;
+    }
+
+    /// Return the velocity in counts per second from the last change, when updated with `sample_hz` Hz
+    pub fn velocity(&self, sample_hz: u32) -> i64 {
+        i64::from(self.delta) * i64::from(sample_hz)
+    }
+}
+
+/// An FTM in quadrature decoder mode, only available on FTM1 and FTM2
+pub struct QuadratureDecoder<'a> {
+    ftm: &'a ftm0::RegisterBlock,
+    tracker: PositionTracker,
+}
+
+impl<'a> QuadratureDecoder<'a> {
+    /// Configure and start the decoder
+    ///
+    /// The clock guard is taken as proof that the FTM is FTM1 or FTM2, and `ftm` must be the FTM
+    /// of the clock guard. The decoder runs from its functional clock, which must be enabled
+    /// with a source. The phase inputs must be muxed to the FTM channel 0 and 1 pins.
+    pub fn init<P: QuadratureTimer>(
+        ftm: &'a ftm0::RegisterBlock,
+        pcc_ftm: &'a P,
+        spc: &spc::Spc,
+        config: QuadratureConfig,
+    ) -> Result<Self, Error> {
+        check_timer::<P>(ftm)?;
+        pcc_ftm.functional_clock_freq(spc).ok_or(Error::NoClock)?;
+        if config.filter_a > FILTER_MAX || config.filter_b > FILTER_MAX {
+            return Err(Error::InvalidFilter);
+        }
+
+        ftm.sc.write(|w| unsafe { w.bits(0) });
+        ftm.mode
+            .modify(|_, w| w.wpdis().set_bit().ftmen().set_bit());
+        ftm.cntin.write(|w| unsafe { w.bits(0) });
+        ftm.mod_.write(|w| unsafe { w.bits(0xFFFF) });
+        ftm.cnt.write(|w| unsafe { w.bits(0) });
+        ftm.filter.modify(|r, w| unsafe {
+            w.bits(
+                (r.bits() & !0xFF) | (u32::from(config.filter_b) << 4) | u32::from(config.filter_a),
+            )
+        });
+
+        let qdctrl = (((config.filter_a > 0) as u32) << 7)
+            | (((config.filter_b > 0) as u32) << 6)
+            | ((config.invert_a as u32) << 5)
+            | ((config.invert_b as u32) << 4)
+            | (((config.mode == QuadratureMode::CountDirection) as u32) << 3)
+            | 1;
+        ftm.qdctrl.write(|w| unsafe { w.bits(qdctrl) });
+        ftm.sc.write(|w| unsafe { w.clks().bits(0b11) });
+
+        Ok(QuadratureDecoder {
+            ftm: ftm,
+            tracker: PositionTracker::new(0),
+        })
+    }
+
+    /// Return the hardware counter
+    pub fn raw_count(&self) -> u16 {
+        self.ftm.cnt.read().bits() as u16
+    }
+
+    /// Return the direction of the last counted edge
+    pub fn direction(&self) -> Direction {
+        if self.ftm.qdctrl.read().quadir().bit_is_set() {
+            Direction::Up
+        } else {
+            Direction::Down
+        }
+    }
+
+    /// Sample the counter and return the change since the last sample
+    ///
+    /// Call this periodically, at least once every 32767 counts.
+    pub fn update(&mut self) -> i16 {
+        let raw = self.raw_count();
+        self.tracker.update(raw)
+    }
+
+    /// Return the position at the last `update`
+    pub fn position(&self) -> i64 {
+        self.tracker.position()
+    }
+
+    pub fn set_position(&mut self, position: i64) {
+        self.tracker.set_position(position);
+    }
+
+    /// Return the velocity in counts per second, when `update` is called with `sample_hz` Hz
+    pub fn velocity(&self, sample_hz: u32) -> i64 {
+        self.tracker.velocity(sample_hz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prescaler_edge() {
+        assert_eq!(
+            find_prescaler(48_000_000, 20_000, Alignment::Edge),
+            Ok((0, 2399))
+        );
+        assert_eq!(
+            find_prescaler(48_000_000, 100, Alignment::Edge),
+            Ok((3, 59_999))
+        );
+    }
+
+    #[test]
+    fn prescaler_center() {
+        assert_eq!(
+            find_prescaler(48_000_000, 20_000, Alignment::Center),
+            Ok((0, 1200))
+        );
+        assert_eq!(
+            find_prescaler(48_000_000, 100, Alignment::Center),
+            Ok((3, 30_000))
+        );
+    }
+
+    #[test]
+    fn prescaler_edge_modulo_limit() {
+        assert_eq!(
+            find_prescaler(65_535_000, 1_000, Alignment::Edge),
+            Ok((0, 0xFFFE))
+        );
+        assert_eq!(
+            find_prescaler(65_536_000, 1_000, Alignment::Edge),
+            Ok((1, 0x7FFF))
+        );
+    }
+
+    #[test]
+    fn prescaler_center_modulo_limit() {
+        assert_eq!(
+            find_prescaler(65_534_000, 1_000, Alignment::Center),
+            Ok((0, 0x7FFF))
+        );
+        assert_eq!(
+            find_prescaler(65_536_000, 1_000, Alignment::Center),
+            Ok((1, 0x4000))
+        );
+    }
+
+    #[test]
+    fn prescaler_out_of_range() {
+        for &alignment in [Alignment::Edge, Alignment::Center].iter() {
+            assert_eq!(
+                find_prescaler(48_000_000, 0, alignment),
+                Err(Error::FrequencyOutOfRange)
+            );
+            // Less than two counts per period
+            assert_eq!(
+                find_prescaler(48_000_000, 30_000_000, alignment),
+                Err(Error::FrequencyOutOfRange)
+            );
+            // Too many counts per period even with the largest prescaler
+            assert_eq!(
+                find_prescaler(48_000_000, 1, alignment),
+                Err(Error::FrequencyOutOfRange)
+            );
+        }
+        assert_eq!(
+            find_prescaler(48_000_000, u32::max_value(), Alignment::Center),
+            Err(Error::FrequencyOutOfRange)
+        );
+    }
+
+    #[test]
+    fn deadtime() {
+        assert_eq!(find_deadtime(48_000_000, 0), Ok((0b00, 0)));
+        assert_eq!(find_deadtime(48_000_000, 1_000), Ok((0b00, 48)));
+        assert_eq!(find_deadtime(48_000_000, 2_000), Ok((0b10, 24)));
+        assert_eq!(find_deadtime(48_000_000, 10_000), Ok((0b11, 30)));
+        assert_eq!(find_deadtime(48_000_000, 21_000), Ok((0b11, 63)));
+    }
+
+    #[test]
+    fn deadtime_out_of_range() {
+        assert_eq!(
+            find_deadtime(48_000_000, 30_000),
+            Err(Error::DeadtimeOutOfRange)
+        );
+        assert_eq!(
+            find_deadtime(u32::max_value(), u32::max_value()),
+            Err(Error::DeadtimeOutOfRange)
+        );
+    }
+
+    #[test]
+    fn position_over_counter_wrap() {
+        let mut tracker = PositionTracker::new(0xFFF0);
+        assert_eq!(tracker.update(0x0010), 0x20);
+        assert_eq!(tracker.update(0xFFE0), -0x30);
+        assert_eq!(tracker.position(), -0x10);
+    }
+
+    #[test]
+    fn velocity_does_not_overflow() {
+        let mut tracker = PositionTracker::new(0);
+        tracker.update(0x7FFF);
+        assert_eq!(tracker.velocity(1_000_000), 0x7FFF * 1_000_000);
+        tracker.update(0);
+        assert_eq!(
+            tracker.velocity(u32::max_value()),
+            -0x7FFF * i64::from(u32::max_value())
+        );
+    }
+}
@@ -0,0 +1,532 @@
+//! General Purpose Input/Output (GPIO)
+//!
+//! Each port is split into pins with their mode in the type, so a pin can only be used in the way
+//! it's configured. The pins implement the `embedded_hal::digital::v2` traits.
+//!
+//! ```rust
+//! let pcc = Pcc::init(&p.PCC);
+//! let pcc_portd = pcc.enable_portd().unwrap();
+//! let gpiod = gpio::GpioD::split(&p.PORTD, &p.PTD, &pcc_portd);
+//!
+//! let mut red: gpio::PTD15<gpio::Output<gpio::PushPull>> = gpiod.ptd15.into_push_pull_output();
+//! red.set_low().unwrap();
+//! ```
+//!
+//! The pins are not touched by `split`, and are considered `Disabled` until they are configured.
+//!
+//! Pin interrupts are configured on each pin with `set_interrupt`. The interrupt flags of the
+//! whole port, and the digital filter settings, are available through the `control` field.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use cortex_m;
+use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
+use s32k144;
+
+use crate::pcc;
+
+/// Pin mux is ALT0, the pin is disabled or used as analog input (type state)
+pub struct Disabled;
+
+/// Input mode (type state)
+pub struct Input<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+/// Floating input (type state)
+pub struct Floating;
+
+/// Pulled up input (type state)
+pub struct PullUp;
+
+/// Pulled down input (type state)
+pub struct PullDown;
+
+/// Output mode (type state)
+pub struct Output<MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+/// Push pull output (type state)
+pub struct PushPull;
+
+/// Pin muxed to a peripheral (type state)
+pub struct Alternate<MUX> {
+    _mux: PhantomData<MUX>,
+}
+
+/// A pin mux alternative that connects the pin to a peripheral
+///
+/// See the signal multiplexing chapter in the reference manual for the function of each alternative.
+pub trait AlternateMux {
+    /// The value of the `MUX` field
+    const MUX: u8;
+}
+
+macro_rules! alternate_mux {
+    ($($(#[$meta:meta])* $Alt:ident: $mux:expr,)+) => {
+        $(
+            $(#[$meta])*
+            pub struct $Alt;
+
+            impl AlternateMux for $Alt {
+                const MUX: u8 = $mux;
+            }
+        )+
+    };
+}
+
+alternate_mux! {
+    /// Alternative 2
+    Alt2: 0b010,
+    /// Alternative 3
+    Alt3: 0b011,
+    /// Alternative 4
+    Alt4: 0b100,
+    /// Alternative 5
+    Alt5: 0b101,
+    /// Alternative 6
+    Alt6: 0b110,
+    /// Alternative 7
+    Alt7: 0b111,
+}
+
+/// Drive strength of an output
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DriveStrength {
+    /// Low drive strength
+    Low,
+
+    /// High drive strength, only available on some pins
+    High,
+}
+
+/// Interrupt or DMA request generated by a pin
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PinInterrupt {
+    /// No interrupt or DMA request
+    Disabled,
+
+    /// DMA request on rising edge
+    DmaRising,
+
+    /// DMA request on falling edge
+    DmaFalling,
+
+    /// DMA request on either edge
+    DmaEither,
+
+    /// Interrupt while the input is low
+    Low,
+
+    /// Interrupt on rising edge
+    Rising,
+
+    /// Interrupt on falling edge
+    Falling,
+
+    /// Interrupt on either edge
+    Either,
+
+    /// Interrupt while the input is high
+    High,
+}
+
+impl PinInterrupt {
+    fn irqc(self) -> u8 {
+        match self {
+            PinInterrupt::Disabled => 0b0000,
+            PinInterrupt::DmaRising => 0b0001,
+            PinInterrupt::DmaFalling => 0b0010,
+            PinInterrupt::DmaEither => 0b0011,
+            PinInterrupt::Low => 0b1000,
+            PinInterrupt::Rising => 0b1001,
+            PinInterrupt::Falling => 0b1010,
+            PinInterrupt::Either => 0b1011,
+            PinInterrupt::High => 0b1100,
+        }
+    }
+}
+
+/// Clock used by the digital input filter of a port
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FilterClock {
+    /// The bus clock
+    Bus,
+
+    /// The 128 kHz low power oscillator
+    Lpo,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The digital filter width doesn't fit in DFWR
+    FilterWidthOutOfRange,
+}
+
+/// Maximum width of the digital filter, in filter clock cycles
+pub const FILTER_WIDTH_MAX: u8 = 31;
+
+/// Value of the `MUX` field for GPIO
+const MUX_GPIO: u8 = 0b001;
+
+macro_rules! gpio {
+    ($Gpio:ident, $Control:ident, $port:ident, $gpio:ident, $PccPort:ident, $doc:expr, [
+        $($PXi:ident: ($pxi:ident, $i:expr, $pcr:ident),)+
+    ]) => {
+        #[doc = $doc]
+        pub struct $Gpio<'a> {
+            /// Interrupt flags and digital filter of the port
+            pub control: $Control<'a>,
+            $(
+                pub $pxi: $PXi<'a, Disabled>,
+            )+
+        }
+
+        impl<'a> $Gpio<'a> {
+            /// Split the port into pins
+            ///
+            /// The clock guard is taken as proof that the port is clocked while the pins are alive.
+            pub fn split(
+                port: &'a s32k144::$port::RegisterBlock,
+                gpio: &'a s32k144::$gpio::RegisterBlock,
+                _pcc: &'a pcc::$PccPort<'a>,
+            ) -> Self {
+                $Gpio {
+                    control: $Control { port: port },
+                    $(
+                        $pxi: $PXi {
+                            port: port,
+                            gpio: gpio,
+                            _mode: PhantomData,
+                        },
+                    )+
+                }
+            }
+        }
+
+        /// Settings and flags common for all pins of the port
+        pub struct $Control<'a> {
+            port: &'a s32k144::$port::RegisterBlock,
+        }
+
+        impl<'a> $Control<'a> {
+            /// Return the interrupt flags of the port, one bit for each pin
+            pub fn interrupt_flags(&self) -> u32 {
+                self.port.isfr.read().bits()
+            }
+
+            /// Clear the interrupt flags set in `mask`
+            pub fn clear_interrupt_flags(&mut self, mask: u32) {
+                self.port.isfr.write(|w| unsafe { w.bits(mask) });
+            }
+
+            /// Configure the digital filter used by the pins with the digital filter enabled
+            ///
+            /// Pulses shorter than `width` cycles of the filter clock are ignored. The
+            /// configuration should only be changed while the filter is disabled on all pins.
+            pub fn set_digital_filter(
+                &mut self,
+                clock: FilterClock,
+                width: u8,
+            ) -> Result<(), Error> {
+                if width > FILTER_WIDTH_MAX {
+                    return Err(Error::FilterWidthOutOfRange);
+                }
+                self.port
+                    .dfcr
+                    .write(|w| w.cs().bit(clock == FilterClock::Lpo));
+                self.port.dfwr.write(|w| unsafe { w.filt().bits(width) });
+                Ok(())
+            }
+        }
+
+        $(
+            /// A pin of the port, with its mode as type state
+            pub struct $PXi<'a, MODE> {
+                port: &'a s32k144::$port::RegisterBlock,
+                gpio: &'a s32k144::$gpio::RegisterBlock,
+                _mode: PhantomData<MODE>,
+            }
+
+            impl<'a, MODE> $PXi<'a, MODE> {
+                const MASK: u32 = 1 << $i;
+
+                fn into_mode<NEW>(self) -> $PXi<'a, NEW> {
+                    $PXi {
+                        port: self.port,
+                        gpio: self.gpio,
+                        _mode: PhantomData,
+                    }
+                }
+
+                /// Modify the PCR without clearing a pending interrupt flag
+                fn modify_pcr<F>(&self, f: F)
+                where
+                    F: for<'w> FnOnce(
+                        &'w mut s32k144::$port::$pcr::W,
+                    ) -> &'w mut s32k144::$port::$pcr::W,
+                {
+                    self.port.$pcr.modify(|_, w| f(w).isf().clear_bit());
+                }
+
+                fn set_direction(&self, output: bool) {
+                    let gpio = self.gpio;
+                    cortex_m::interrupt::free(|_cs| {
+                        gpio.pddr.modify(|r, w| unsafe {
+                            if output {
+                                w.bits(r.bits() | Self::MASK)
+                            } else {
+                                w.bits(r.bits() & !Self::MASK)
+                            }
+                        })
+                    });
+                }
+
+                /// Configure the pin as push pull output
+                pub fn into_push_pull_output(self) -> $PXi<'a, Output<PushPull>> {
+                    self.set_direction(true);
+                    self.modify_pcr(|w| w.mux().bits(MUX_GPIO).pe().clear_bit());
+                    self.into_mode()
+                }
+
+                /// Configure the pin as floating input
+                pub fn into_floating_input(self) -> $PXi<'a, Input<Floating>> {
+                    self.set_direction(false);
+                    self.modify_pcr(|w| w.mux().bits(MUX_GPIO).pe().clear_bit());
+                    self.into_mode()
+                }
+
+                /// Configure the pin as pulled up input
+                pub fn into_pull_up_input(self) -> $PXi<'a, Input<PullUp>> {
+                    self.set_direction(false);
+                    self.modify_pcr(|w| {
+                        w.mux().bits(MUX_GPIO).pe().set_bit().ps().set_bit()
+                    });
+                    self.into_mode()
+                }
+
+                /// Configure the pin as pulled down input
+                pub fn into_pull_down_input(self) -> $PXi<'a, Input<PullDown>> {
+                    self.set_direction(false);
+                    self.modify_pcr(|w| {
+                        w.mux().bits(MUX_GPIO).pe().set_bit().ps().clear_bit()
+                    });
+                    self.into_mode()
+                }
+
+                /// Mux the pin to a peripheral
+                pub fn into_alternate<MUX: AlternateMux>(self) -> $PXi<'a, Alternate<MUX>> {
+                    self.modify_pcr(|w| w.mux().bits(MUX::MUX));
+                    self.into_mode()
+                }
+
+                /// Disable the pin, this is also used for analog inputs
+                pub fn into_disabled(self) -> $PXi<'a, Disabled> {
+                    self.modify_pcr(|w| w.mux().bits(0b000).pe().clear_bit());
+                    self.into_mode()
+                }
+
+                /// Select the interrupt or DMA request generated by the pin
+                pub fn set_interrupt(&mut self, interrupt: PinInterrupt) {
+                    self.modify_pcr(|w| unsafe { w.irqc().bits(interrupt.irqc()) });
+                }
+
+                /// Return true if the interrupt flag of the pin is set
+                pub fn is_interrupt_pending(&self) -> bool {
+                    self.port.isfr.read().bits() & Self::MASK != 0
+                }
+
+                /// Clear the interrupt flag of the pin
+                pub fn clear_interrupt_pending(&mut self) {
+                    self.port.isfr.write(|w| unsafe { w.bits(Self::MASK) });
+                }
+
+                /// Set the drive strength used when the pin is an output
+                pub fn set_drive_strength(&mut self, strength: DriveStrength) {
+                    self.modify_pcr(|w| w.dse().bit(strength == DriveStrength::High));
+                }
+            }
+
+            impl<'a, MODE> $PXi<'a, Input<MODE>> {
+                /// Enable or disable the passive input filter
+                pub fn set_passive_filter(&mut self, enable: bool) {
+                    self.modify_pcr(|w| w.pfe().bit(enable));
+                }
+
+                /// Enable or disable the digital input filter
+                ///
+                /// The filter clock and width are common for the port, see `set_digital_filter` on
+                /// the port control.
+                pub fn set_digital_filter(&mut self, enable: bool) {
+                    let port = self.port;
+                    cortex_m::interrupt::free(|_cs| {
+                        port.dfer.modify(|r, w| unsafe {
+                            if enable {
+                                w.bits(r.bits() | Self::MASK)
+                            } else {
+                                w.bits(r.bits() & !Self::MASK)
+                            }
+                        })
+                    });
+                }
+            }
+
+            impl<'a, MODE> OutputPin for $PXi<'a, Output<MODE>> {
+                type Error = Infallible;
+
+                fn set_low(&mut self) -> Result<(), Infallible> {
+                    self.gpio
+                        .pcor
+                        .write(|w| unsafe { w.ptco().bits(Self::MASK) });
+                    Ok(())
+                }
+
+                fn set_high(&mut self) -> Result<(), Infallible> {
+                    self.gpio
+                        .psor
+                        .write(|w| unsafe { w.ptso().bits(Self::MASK) });
+                    Ok(())
+                }
+            }
+
+            impl<'a, MODE> StatefulOutputPin for $PXi<'a, Output<MODE>> {
+                fn is_set_high(&self) -> Result<bool, Infallible> {
+                    Ok(self.gpio.pdor.read().pdo().bits() & Self::MASK != 0)
+                }
+
+                fn is_set_low(&self) -> Result<bool, Infallible> {
+                    Ok(self.gpio.pdor.read().pdo().bits() & Self::MASK == 0)
+                }
+            }
+
+            impl<'a, MODE> ToggleableOutputPin for $PXi<'a, Output<MODE>> {
+                type Error = Infallible;
+
+                fn toggle(&mut self) -> Result<(), Infallible> {
+                    self.gpio
+                        .ptor
+                        .write(|w| unsafe { w.ptto().bits(Self::MASK) });
+                    Ok(())
+                }
+            }
+
+            impl<'a, MODE> InputPin for $PXi<'a, Input<MODE>> {
+                type Error = Infallible;
+
+                fn is_high(&self) -> Result<bool, Infallible> {
+                    Ok(self.gpio.pdir.read().pdi().bits() & Self::MASK != 0)
+                }
+
+                fn is_low(&self) -> Result<bool, Infallible> {
+                    Ok(self.gpio.pdir.read().pdi().bits() & Self::MASK == 0)
+                }
+            }
+        )+
+    };
+}
+
+gpio!(GpioA, ControlA, porta, pta, PortA, "Port A split into pins", [
+    PTA0: (pta0, 0, pcr0),
+    PTA1: (pta1, 1, pcr1),
+    PTA2: (pta2, 2, pcr2),
+    PTA3: (pta3, 3, pcr3),
+    PTA4: (pta4, 4, pcr4),
+    PTA5: (pta5, 5, pcr5),
+    PTA6: (pta6, 6, pcr6),
+    PTA7: (pta7, 7, pcr7),
+    PTA8: (pta8, 8, pcr8),
+    PTA9: (pta9, 9, pcr9),
+    PTA10: (pta10, 10, pcr10),
+    PTA11: (pta11, 11, pcr11),
+    PTA12: (pta12, 12, pcr12),
+    PTA13: (pta13, 13, pcr13),
+    PTA14: (pta14, 14, pcr14),
+    PTA15: (pta15, 15, pcr15),
+    PTA16: (pta16, 16, pcr16),
+    PTA17: (pta17, 17, pcr17),
+]);
+
+gpio!(GpioB, ControlB, portb, ptb, PortB, "Port B split into pins", [
+    PTB0: (ptb0, 0, pcr0),
+    PTB1: (ptb1, 1, pcr1),
+    PTB2: (ptb2, 2, pcr2),
+    PTB3: (ptb3, 3, pcr3),
+    PTB4: (ptb4, 4, pcr4),
+    PTB5: (ptb5, 5, pcr5),
+    PTB6: (ptb6, 6, pcr6),
+    PTB7: (ptb7, 7, pcr7),
+    PTB8: (ptb8, 8, pcr8),
+    PTB9: (ptb9, 9, pcr9),
+    PTB10: (ptb10, 10, pcr10),
+    PTB11: (ptb11, 11, pcr11),
+    PTB12: (ptb12, 12, pcr12),
+    PTB13: (ptb13, 13, pcr13),
+    PTB14: (ptb14, 14, pcr14),
+    PTB15: (ptb15, 15, pcr15),
+    PTB16: (ptb16, 16, pcr16),
+    PTB17: (ptb17, 17, pcr17),
+]);
+
+gpio!(GpioC, ControlC, portc, ptc, PortC, "Port C split into pins", [
+    PTC0: (ptc0, 0, pcr0),
+    PTC1: (ptc1, 1, pcr1),
+    PTC2: (ptc2, 2, pcr2),
+    PTC3: (ptc3, 3, pcr3),
+    PTC4: (ptc4, 4, pcr4),
+    PTC5: (ptc5, 5, pcr5),
+    PTC6: (ptc6, 6, pcr6),
+    PTC7: (ptc7, 7, pcr7),
+    PTC8: (ptc8, 8, pcr8),
+    PTC9: (ptc9, 9, pcr9),
+    PTC10: (ptc10, 10, pcr10),
+    PTC11: (ptc11, 11, pcr11),
+    PTC12: (ptc12, 12, pcr12),
+    PTC13: (ptc13, 13, pcr13),
+    PTC14: (ptc14, 14, pcr14),
+    PTC15: (ptc15, 15, pcr15),
+    PTC16: (ptc16, 16, pcr16),
+    PTC17: (ptc17, 17, pcr17),
+]);
+
+gpio!(GpioD, ControlD, portd, ptd, PortD, "Port D split into pins", [
+    PTD0: (ptd0, 0, pcr0),
+    PTD1: (ptd1, 1, pcr1),
+    PTD2: (ptd2, 2, pcr2),
+    PTD3: (ptd3, 3, pcr3),
+    PTD4: (ptd4, 4, pcr4),
+    PTD5: (ptd5, 5, pcr5),
+    PTD6: (ptd6, 6, pcr6),
+    PTD7: (ptd7, 7, pcr7),
+    PTD8: (ptd8, 8, pcr8),
+    PTD9: (ptd9, 9, pcr9),
+    PTD10: (ptd10, 10, pcr10),
+    PTD11: (ptd11, 11, pcr11),
+    PTD12: (ptd12, 12, pcr12),
+    PTD13: (ptd13, 13, pcr13),
+    PTD14: (ptd14, 14, pcr14),
+    PTD15: (ptd15, 15, pcr15),
+    PTD16: (ptd16, 16, pcr16),
+    PTD17: (ptd17, 17, pcr17),
+]);
+
+gpio!(GpioE, ControlE, porte, pte, PortE, "Port E split into pins", [
+    PTE0: (pte0, 0, pcr0),
+    PTE1: (pte1, 1, pcr1),
+    PTE2: (pte2, 2, pcr2),
+    PTE3: (pte3, 3, pcr3),
+    PTE4: (pte4, 4, pcr4),
+    PTE5: (pte5, 5, pcr5),
+    PTE6: (pte6, 6, pcr6),
+    PTE7: (pte7, 7, pcr7),
+    PTE8: (pte8, 8, pcr8),
+    PTE9: (pte9, 9, pcr9),
+    PTE10: (pte10, 10, pcr10),
+    PTE11: (pte11, 11, pcr11),
+    PTE12: (pte12, 12, pcr12),
+    PTE13: (pte13, 13, pcr13),
+    PTE14: (pte14, 14, pcr14),
+    PTE15: (pte15, 15, pcr15),
+    PTE16: (pte16, 16, pcr16),
+]);
@@ -2,7 +2,9 @@
 
 extern crate cortex_m;
 
-use crate::pcc;
+use embedded_hal::PwmPin;
+
+use crate::{ftm, pcc, spc};
 use s32k144;
 
 pub struct RgbLed<'a> {
@@ -73,5 +75,213 @@ impl<'a> RgbLed<'a> {
         }
     }
 
-    pub fn off(&self) {}
+    pub fn off(&self) {
+        self.set(false, false, false);
+    }
+}
+
+/// An 8-bit per channel color
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb::new(0, 0, 0);
+    pub const WHITE: Rgb = Rgb::new(255, 255, 255);
+    pub const RED: Rgb = Rgb::new(255, 0, 0);
+    pub const GREEN: Rgb = Rgb::new(0, 255, 0);
+    pub const BLUE: Rgb = Rgb::new(0, 0, 255);
+    pub const YELLOW: Rgb = Rgb::new(255, 255, 0);
+    pub const CYAN: Rgb = Rgb::new(0, 255, 255);
+    pub const MAGENTA: Rgb = Rgb::new(255, 0, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r: r, g: g, b: b }
+    }
+
+    /// Return the color `step` of `steps` along the way from `self` to `to`
+    pub fn interpolate(self, to: Rgb, step: u16, steps: u16) -> Rgb {
+        fn channel(from: u8, to: u8, step: u16, steps: u16) -> u8 {
+            let from = i32::from(from);
+            let to = i32::from(to);
+            (from + (to - from) * i32::from(step) / i32::from(steps)) as u8
+        }
+
+        if steps == 0 || step >= steps {
+            return to;
+        }
+
+        Rgb {
+            r: channel(self.r, to.r, step, steps),
+            g: channel(self.g, to.g, step, steps),
+            b: channel(self.b, to.b, step, steps),
+        }
+    }
+}
+
+/// Map an 8-bit channel value to a 16-bit duty cycle that looks linear to the eye
+///
+/// The gamma curve is approximated with a power of two.
+pub fn gamma(value: u8) -> u16 {
+    let value = u32::from(value);
+    (value * value * 0xFFFF / (255 * 255)) as u16
+}
+
+/// A fade between two colors, yielding one color per step and ending at the target color
+#[derive(Debug, Clone, Copy)]
+pub struct Fade {
+    from: Rgb,
+    to: Rgb,
+    step: u16,
+    steps: u16,
+}
+
+impl Fade {
+    pub fn new(from: Rgb, to: Rgb, steps: u16) -> Self {
+        Fade {
+            from: from,
+            to: to,
+            step: 0,
+            steps: if steps == 0 { 1 } else { steps },
+        }
+    }
+}
+
+impl Iterator for Fade {
+    type Item = Rgb;
+
+    fn next(&mut self) -> Option<Rgb> {
+        if self.step >= self.steps {
+            return None;
+        }
+        self.step += 1;
+        Some(self.from.interpolate(self.to, self.step, self.steps))
+    }
+}
+
+/// The RGB LED driven with PWM from FTM0
+///
+/// Red is on FTM0 CH0 (PTD15), green on FTM0 CH1 (PTD16) and blue on FTM0 CH2 (PTD0).
+pub struct PwmRgbLed<'a> {
+    ftm: ftm::Ftm<'a>,
+    red: ftm::PwmChannel<'a>,
+    green: ftm::PwmChannel<'a>,
+    blue: ftm::PwmChannel<'a>,
+    pcc_portd: &'a pcc::PortD<'a>,
+    color: Rgb,
+    brightness: u8,
+    fade: Option<Fade>,
+}
+
+impl<'a> PwmRgbLed<'a> {
+    /// Mux the LED pins to FTM0 and start the PWM at `frequency` Hz, with the LED off
+    ///
+    /// FTM0 is clocked from its PCC functional clock, which must be enabled with a source.
+    pub fn init(
+        ftm0: &'a s32k144::ftm0::RegisterBlock,
+        portd: &'a s32k144::portd::RegisterBlock,
+        pcc_ftm0: &'a pcc::Ftm0,
+        pcc_portd: &'a pcc::PortD,
+        spc: &spc::Spc,
+        frequency: u32,
+    ) -> Result<Self, ftm::Error> {
+        let mut ftm = ftm::Ftm::init(ftm0, pcc_ftm0, spc)?;
+        ftm.start_pwm(frequency, ftm::Alignment::Edge)?;
+
+        // The LED is on while the pin is low
+        let mut red = ftm.pwm_channel(ftm::Channel::Ch0);
+        let mut green = ftm.pwm_channel(ftm::Channel::Ch1);
+        let mut blue = ftm.pwm_channel(ftm::Channel::Ch2);
+        for channel in [&mut red, &mut green, &mut blue].iter_mut() {
+            channel.set_polarity(ftm::Polarity::LowTrue);
+            channel.enable();
+        }
+
+        portd
+            .pcr0
+            .modify(|_, w| w.mux().bits(0b010).dse()._1().pe()._0());
+        portd
+            .pcr15
+            .modify(|_, w| w.mux().bits(0b010).dse()._1().pe()._0());
+        portd
+            .pcr16
+            .modify(|_, w| w.mux().bits(0b010).dse()._1().pe()._0());
+
+        Ok(PwmRgbLed {
+            ftm: ftm,
+            red: red,
+            green: green,
+            blue: blue,
+            pcc_portd: pcc_portd,
+            color: Rgb::BLACK,
+            brightness: 255,
+            fade: None,
+        })
+    }
+
+    /// Set the color immediately, stopping any fade in progress
+    pub fn set_color(&mut self, color: Rgb) {
+        self.fade = None;
+        self.color = color;
+        self.update();
+    }
+
+    /// Return the color currently shown, before brightness is applied
+    pub fn color(&self) -> Rgb {
+        self.color
+    }
+
+    /// Set the brightness applied to all colors, where 255 is full brightness
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+        self.update();
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn off(&mut self) {
+        self.set_color(Rgb::BLACK);
+    }
+
+    /// Start fading from the current color to `color` over `steps` calls to `tick`
+    pub fn fade_to(&mut self, color: Rgb, steps: u16) {
+        self.fade = Some(Fade::new(self.color, color, steps));
+    }
+
+    /// Advance a fade in progress, returns true until the fade has finished
+    pub fn tick(&mut self) -> bool {
+        let next = self.fade.as_mut().and_then(|fade| fade.next());
+        match next {
+            Some(color) => {
+                self.color = color;
+                self.update();
+                true
+            }
+            None => {
+                self.fade = None;
+                false
+            }
+        }
+    }
+
+    fn duty(&self, value: u8) -> u16 {
+        let level = u32::from(gamma(value)) * u32::from(self.brightness) / 255;
+        (u32::from(self.red.get_max_duty()) * level / 0xFFFF) as u16
+    }
+
+    fn update(&mut self) {
+        let (r, g, b) = (
+            self.duty(self.color.r),
+            self.duty(self.color.g),
+            self.duty(self.color.b),
+        );
+        self.red.set_duty(r);
+        self.green.set_duty(g);
+        self.blue.set_duty(b);
+    }
 }
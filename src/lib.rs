@@ -1,21 +1,36 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 extern crate bit_field;
 #[cfg_attr(feature = "itm", macro_use)]
 extern crate cortex_m;
 extern crate cortex_m_rt;
+extern crate embedded_hal;
 extern crate embedded_types;
 extern crate s32k144;
 
+pub mod adc;
+pub mod blink;
+pub mod button;
 pub mod can;
 pub mod csec;
+pub mod ftm;
+pub mod gpio;
 pub mod led;
+pub mod lpi2c;
+pub mod lpit;
+pub mod lpspi;
+pub mod lptmr;
 pub mod lpuart;
 pub mod pcc;
+pub mod pmc;
+pub mod sbc;
 pub mod spc;
 pub mod wdog;
 
 pub mod console;
 
-#[cfg(any(feature = "panic-over-itm", feature = "panic-over-serial"))]
+#[cfg(all(
+    not(test),
+    any(feature = "panic-over-itm", feature = "panic-over-serial")
+))]
 mod panic;
@@ -0,0 +1,676 @@
+//! The Low Power Inter-Integrated Circuit module (LPI2C0)
+//!
+//! The module has independent master and slave logic. The master is clocked from the LPI2C0 PCC
+//! functional clock, and implements the `embedded-hal` blocking I2C traits for 7-bit addresses.
+//!
+//! ```rust
+//! let pcc_lpi2c0: pcc::Lpi2c0 = pcc.enable_with_source(pcc::ClockSource::Sircdiv2).unwrap();
+//! let config = lpi2c::MasterConfig::default();
+//! let mut i2c = lpi2c::Master::init(&p.LPI2C0, &pcc_lpi2c0, &spc, config).unwrap();
+//! let mut data = [0u8; 2];
+//! i2c.write_read(0x50, &[0x00], &mut data).unwrap();
+//! ```
+
+#![allow(dead_code)]
+
+use embedded_hal::blocking::i2c;
+use s32k144::lpi2c0;
+
+use crate::{pcc, spc};
+
+/// Master control register bits
+const MCR_MEN: u32 = 1 << 0;
+const MCR_RST: u32 = 1 << 1;
+const MCR_DBGEN: u32 = 1 << 3;
+const MCR_RTF: u32 = 1 << 8;
+const MCR_RRF: u32 = 1 << 9;
+
+/// Master status register bits
+const MSR_TDF: u32 = 1 << 0;
+const MSR_SDF: u32 = 1 << 9;
+const MSR_NDF: u32 = 1 << 10;
+const MSR_ALF: u32 = 1 << 11;
+const MSR_FEF: u32 = 1 << 12;
+const MSR_PLTF: u32 = 1 << 13;
+const MSR_MBF: u32 = 1 << 24;
+const MSR_BBF: u32 = 1 << 25;
+const MSR_ERRORS: u32 = MSR_NDF | MSR_ALF | MSR_FEF | MSR_PLTF;
+
+/// Master transmit data register commands
+const CMD_TRANSMIT: u32 = 0b000 << 8;
+const CMD_RECEIVE: u32 = 0b001 << 8;
+const CMD_STOP: u32 = 0b010 << 8;
+const CMD_START: u32 = 0b100 << 8;
+
+/// Receive data register empty flag, in MRDR and SRDR
+const RDR_RXEMPTY: u32 = 1 << 14;
+
+/// Slave control register bits
+const SCR_SEN: u32 = 1 << 0;
+const SCR_RST: u32 = 1 << 1;
+const SCR_FILTEN: u32 = 1 << 4;
+
+/// Slave status register bits
+const SSR_TDF: u32 = 1 << 0;
+const SSR_RDF: u32 = 1 << 1;
+const SSR_AVF: u32 = 1 << 2;
+const SSR_RSF: u32 = 1 << 8;
+const SSR_SDF: u32 = 1 << 9;
+const SSR_BEF: u32 = 1 << 10;
+const SSR_FEF: u32 = 1 << 11;
+
+/// Slave configuration register 1 bits
+const SCFGR1_ADRSTALL: u32 = 1 << 0;
+const SCFGR1_RXSTALL: u32 = 1 << 1;
+const SCFGR1_TXDSTALL: u32 = 1 << 2;
+const SCFGR1_GCEN: u32 = 1 << 8;
+const SCFGR1_ADDRCFG_SHIFT: u32 = 16;
+
+/// Highest CLKLO and CLKHI value
+const CLK_MAX: u32 = 63;
+
+/// Lowest CLKLO value
+const CLKLO_MIN: u32 = 3;
+
+/// Most bytes received by one receive command
+const RECEIVE_MAX: usize = 256;
+
+/// Number of times a status flag is checked before giving up
+const STATUS_CHECKS: u32 = 1_000_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The LPI2C0 functional clock is not running
+    NoClock,
+
+    /// The bus frequency can't be reached from the functional clock
+    UnsatisfiableBaud,
+
+    /// The addressed device, or the device receiving data, didn't acknowledge
+    Nack,
+
+    /// Another master took the bus
+    ArbitrationLost,
+
+    /// A command was given while the master wasn't allowed to, e.g. data before a START
+    FifoError,
+
+    /// A bus pin was held low longer than the pin low timeout
+    PinLowTimeout,
+
+    /// The transfer didn't complete in time
+    Timeout,
+
+    /// The address doesn't fit in the addressing mode
+    InvalidAddress,
+}
+
+/// Bus speed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Speed {
+    /// 100 kHz
+    Standard,
+
+    /// 400 kHz
+    Fast,
+
+    /// 1 MHz
+    FastPlus,
+}
+
+impl Speed {
+    pub fn freq(self) -> u32 {
+        match self {
+            Speed::Standard => 100_000,
+            Speed::Fast => 400_000,
+            Speed::FastPlus => 1_000_000,
+        }
+    }
+}
+
+/// A 7-bit or 10-bit device address
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Address {
+    SevenBit(u8),
+    TenBit(u16),
+}
+
+impl Address {
+    fn validate(self) -> Result<(), Error> {
+        match self {
+            Address::SevenBit(address) if address <= 0x7F => Ok(()),
+            Address::TenBit(address) if address <= 0x3FF => Ok(()),
+            _ => Err(Error::InvalidAddress),
+        }
+    }
+}
+
+/// Clock configuration of the master
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Timing {
+    pub prescale: u8,
+    pub clklo: u8,
+    pub clkhi: u8,
+    pub sethold: u8,
+    pub datavd: u8,
+}
+
+/// Find the smallest prescaler where the SCL period fits in CLKLO and CLKHI
+///
+/// The SCL frequency is `source / (2^prescale * (clklo + clkhi + 2 + latency))`, where the latency
+/// is `2 / 2^prescale` with the glitch filters disabled. The low period is made longer than the
+/// high period in fast and fast-plus mode to meet the minimum low time.
+pub fn find_timing(source: u32, speed: Speed) -> Result<Timing, Error> {
+    let baud = speed.freq();
+
+    for prescale in 0..8 {
+        let latency = 2 >> prescale;
+        let total = (source >> prescale) / baud;
+        if total < 2 + latency + CLKLO_MIN + 1 {
+            break;
+        }
+
+        let cycles = total - 2 - latency;
+        let clklo = match speed {
+            Speed::Standard => (cycles + 1) / 2,
+            Speed::Fast | Speed::FastPlus => (cycles * 2 + 2) / 3,
+        };
+        let clkhi = cycles - clklo;
+        if clklo <= CLK_MAX && clkhi <= CLK_MAX {
+            return Ok(Timing {
+                prescale: prescale as u8,
+                clklo: clklo as u8,
+                clkhi: clkhi as u8,
+                sethold: clkhi as u8,
+                datavd: (clkhi / 2) as u8,
+            });
+        }
+    }
+    Err(Error::UnsatisfiableBaud)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MasterConfig {
+    pub speed: Speed,
+
+    /// Cycles of the prescaled functional clock both pins must be high before the bus is
+    /// considered idle, 0 to disable
+    pub bus_idle_timeout: u16,
+
+    /// Cycles of the prescaled functional clock a pin may be held low, 0 to disable
+    pub pin_low_timeout: u16,
+}
+
+impl Default for MasterConfig {
+    fn default() -> Self {
+        MasterConfig {
+            speed: Speed::Standard,
+            bus_idle_timeout: 0,
+            pin_low_timeout: 0,
+        }
+    }
+}
+
+/// The LPI2C master
+///
+/// Devices stretching the clock are waited for.
+pub struct Master<'a> {
+    lpi2c: &'a lpi2c0::RegisterBlock,
+    config: MasterConfig,
+}
+
+impl<'a> Master<'a> {
+    /// Configure and enable the master
+    ///
+    /// The bus clock is divided from the LPI2C0 functional clock, which must be enabled with a
+    /// source.
+    pub fn init(
+        lpi2c: &'a lpi2c0::RegisterBlock,
+        pcc_lpi2c0: &'a pcc::Lpi2c0,
+        spc: &spc::Spc,
+        config: MasterConfig,
+    ) -> Result<Master<'a>, Error> {
+        let source_frequency = pcc_lpi2c0
+            .functional_clock_freq(spc)
+            .ok_or(Error::NoClock)?;
+        let timing = find_timing(source_frequency, config.speed)?;
+
+        lpi2c.mcr.write(|w| unsafe { w.bits(MCR_RST) });
+        lpi2c.mcr.write(|w| unsafe { w.bits(0) });
+
+        lpi2c
+            .mcfgr1
+            .write(|w| unsafe { w.bits(u32::from(timing.prescale)) });
+        lpi2c
+            .mcfgr2
+            .write(|w| unsafe { w.bits(u32::from(config.bus_idle_timeout) & 0xFFF) });
+        lpi2c
+            .mcfgr3
+            .write(|w| unsafe { w.bits((u32::from(config.pin_low_timeout) & 0xFFF) << 8) });
+        lpi2c.mccr0.write(|w| unsafe {
+            w.bits(
+                (u32::from(timing.datavd) << 24)
+                    | (u32::from(timing.sethold) << 16)
+                    | (u32::from(timing.clkhi) << 8)
+                    | u32::from(timing.clklo),
+            )
+        });
+        lpi2c.mfcr.write(|w| unsafe { w.bits(0) });
+
+        lpi2c.mcr.write(|w| unsafe { w.bits(MCR_MEN | MCR_DBGEN) });
+
+        Ok(Master {
+            lpi2c: lpi2c,
+            config: config,
+        })
+    }
+
+    /// Return true while another master is using the bus
+    pub fn is_bus_busy(&self) -> bool {
+        let msr = self.lpi2c.msr.read().bits();
+        msr & MSR_BBF != 0 && msr & MSR_MBF == 0
+    }
+
+    /// Check the error flags, and clean up after an error
+    fn check_errors(&self) -> Result<(), Error> {
+        let msr = self.lpi2c.msr.read().bits();
+        if msr & MSR_ERRORS == 0 {
+            return Ok(());
+        }
+
+        // Discard the rest of the transfer and clear the flags. A STOP is generated by the
+        // hardware after a NACK, and the bus is released after arbitration loss.
+        self.lpi2c
+            .mcr
+            .modify(|r, w| unsafe { w.bits(r.bits() | MCR_RTF | MCR_RRF) });
+        self.lpi2c.msr.write(|w| unsafe { w.bits(MSR_ERRORS) });
+
+        if msr & MSR_NDF != 0 {
+            Err(Error::Nack)
+        } else if msr & MSR_ALF != 0 {
+            Err(Error::ArbitrationLost)
+        } else if msr & MSR_FEF != 0 {
+            Err(Error::FifoError)
+        } else {
+            Err(Error::PinLowTimeout)
+        }
+    }
+
+    /// Put a command in the transmit FIFO, waiting for it to be empty
+    fn command(&self, command: u32) -> Result<(), Error> {
+        let mut checks = 0;
+        while self.lpi2c.msr.read().bits() & MSR_TDF == 0 {
+            self.check_errors()?;
+            checks += 1;
+            if checks >= STATUS_CHECKS {
+                return Err(Error::Timeout);
+            }
+        }
+        self.lpi2c.mtdr.write(|w| unsafe { w.bits(command) });
+        Ok(())
+    }
+
+    fn receive_byte(&self) -> Result<u8, Error> {
+        let mut checks = 0;
+        loop {
+            let mrdr = self.lpi2c.mrdr.read().bits();
+            if mrdr & RDR_RXEMPTY == 0 {
+                return Ok(mrdr as u8);
+            }
+            self.check_errors()?;
+            checks += 1;
+            if checks >= STATUS_CHECKS {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// Generate a START or repeated START and send the address
+    fn start(&self, address: Address, read: bool) -> Result<(), Error> {
+        address.validate()?;
+        let rw = read as u32;
+        match address {
+            Address::SevenBit(address) => self.command(CMD_START | (u32::from(address) << 1) | rw),
+            Address::TenBit(address) => {
+                // The upper address bits are sent with the 0b11110 prefix, and the lower bits as
+                // data. A read is started by a repeated START with only the upper bits.
+                let header = 0b1111_0000 | (u32::from(address >> 8) << 1);
+                self.command(CMD_START | header)?;
+                self.command(CMD_TRANSMIT | u32::from(address & 0xFF))?;
+                if read {
+                    self.command(CMD_START | header | 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Generate a STOP and wait for it on the bus
+    fn stop(&self) -> Result<(), Error> {
+        self.command(CMD_STOP)?;
+
+        let mut checks = 0;
+        while self.lpi2c.msr.read().bits() & MSR_SDF == 0 {
+            self.check_errors()?;
+            checks += 1;
+            if checks >= STATUS_CHECKS {
+                return Err(Error::Timeout);
+            }
+        }
+        self.lpi2c.msr.write(|w| unsafe { w.bits(MSR_SDF) });
+        Ok(())
+    }
+
+    /// End a transfer with a STOP, or release the bus after an error
+    ///
+    /// The hardware generates a STOP after a NACK, and the bus is already lost after arbitration
+    /// loss. After other errors the master may still own the bus, so a STOP is generated, and
+    /// the master is reset if that fails too.
+    fn finish(&self, result: Result<(), Error>) -> Result<(), Error> {
+        let result = result.and_then(|_| self.stop());
+        match result {
+            Ok(()) | Err(Error::Nack) | Err(Error::ArbitrationLost) => result,
+            Err(_) => {
+                self.lpi2c
+                    .mcr
+                    .modify(|r, w| unsafe { w.bits(r.bits() | MCR_RTF | MCR_RRF) });
+                self.lpi2c.msr.write(|w| unsafe { w.bits(MSR_ERRORS) });
+                if self.lpi2c.msr.read().bits() & MSR_MBF != 0 && self.stop().is_err() {
+                    self.reset();
+                }
+                result
+            }
+        }
+    }
+
+    /// Reset the master logic, keeping the configuration
+    fn reset(&self) {
+        let lpi2c = self.lpi2c;
+        let mcfgr1 = lpi2c.mcfgr1.read().bits();
+        let mcfgr2 = lpi2c.mcfgr2.read().bits();
+        let mcfgr3 = lpi2c.mcfgr3.read().bits();
+        let mccr0 = lpi2c.mccr0.read().bits();
+
+        lpi2c.mcr.write(|w| unsafe { w.bits(MCR_RST) });
+        lpi2c.mcr.write(|w| unsafe { w.bits(0) });
+
+        lpi2c.mcfgr1.write(|w| unsafe { w.bits(mcfgr1) });
+        lpi2c.mcfgr2.write(|w| unsafe { w.bits(mcfgr2) });
+        lpi2c.mcfgr3.write(|w| unsafe { w.bits(mcfgr3) });
+        lpi2c.mccr0.write(|w| unsafe { w.bits(mccr0) });
+        lpi2c.mfcr.write(|w| unsafe { w.bits(0) });
+
+        lpi2c.mcr.write(|w| unsafe { w.bits(MCR_MEN | MCR_DBGEN) });
+    }
+
+    fn transmit(&self, bytes: &[u8]) -> Result<(), Error> {
+        for &byte in bytes {
+            self.command(CMD_TRANSMIT | u32::from(byte))?;
+        }
+        Ok(())
+    }
+
+    fn receive(&self, buffer: &mut [u8]) -> Result<(), Error> {
+        for chunk in buffer.chunks_mut(RECEIVE_MAX) {
+            self.command(CMD_RECEIVE | (chunk.len() as u32 - 1))?;
+            for byte in chunk.iter_mut() {
+                *byte = self.receive_byte()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write bytes to a device
+    pub fn write_to(&mut self, address: Address, bytes: &[u8]) -> Result<(), Error> {
+        let result = self
+            .start(address, false)
+            .and_then(|_| self.transmit(bytes));
+        self.finish(result)
+    }
+
+    /// Read bytes from a device
+    pub fn read_from(&mut self, address: Address, buffer: &mut [u8]) -> Result<(), Error> {
+        let result = self.start(address, true).and_then(|_| self.receive(buffer));
+        self.finish(result)
+    }
+
+    /// Write bytes to a device, then read from it after a repeated START
+    pub fn write_read_from(
+        &mut self,
+        address: Address,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        let result = self
+            .start(address, false)
+            .and_then(|_| self.transmit(bytes))
+            .and_then(|_| self.start(address, true))
+            .and_then(|_| self.receive(buffer));
+        self.finish(result)
+    }
+}
+
+impl<'a> i2c::Write for Master<'a> {
+    type Error = Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.write_to(Address::SevenBit(address), bytes)
+    }
+}
+
+impl<'a> i2c::Read for Master<'a> {
+    type Error = Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.read_from(Address::SevenBit(address), buffer)
+    }
+}
+
+impl<'a> i2c::WriteRead for Master<'a> {
+    type Error = Error;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        self.write_read_from(Address::SevenBit(address), bytes, buffer)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SlaveConfig {
+    pub address: Address,
+
+    /// Also respond to the general call address
+    pub general_call: bool,
+
+    /// Hold SCL low after the address, and while waiting for the software to read or write data
+    pub clock_stretching: bool,
+}
+
+/// Something that happened on the bus, addressed to the slave
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SlaveEvent {
+    /// The slave was addressed, `read` is true if the master reads from the slave
+    AddressMatch { read: bool },
+
+    /// A byte was received from the master
+    Received(u8),
+
+    /// The master reads, and a byte must be given with `Slave::transmit`
+    TransmitRequest,
+
+    /// A repeated START was detected
+    RepeatedStart,
+
+    /// A STOP was detected
+    Stop,
+
+    /// A bit error or FIFO error occured
+    Error,
+}
+
+/// The LPI2C slave
+pub struct Slave<'a> {
+    lpi2c: &'a lpi2c0::RegisterBlock,
+    config: SlaveConfig,
+}
+
+impl<'a> Slave<'a> {
+    /// Configure and enable the slave
+    ///
+    /// The clock guard keeps the LPI2C0 clock gated on, the slave doesn't depend on its frequency.
+    pub fn init(
+        lpi2c: &'a lpi2c0::RegisterBlock,
+        _pcc_lpi2c0: &'a pcc::Lpi2c0,
+        config: SlaveConfig,
+    ) -> Result<Slave<'a>, Error> {
+        config.address.validate()?;
+
+        lpi2c.scr.write(|w| unsafe { w.bits(SCR_RST) });
+        lpi2c.scr.write(|w| unsafe { w.bits(0) });
+
+        // ADDRCFG 0b000 matches a 7-bit address, 0b001 a 10-bit address
+        let (addrcfg, address) = match config.address {
+            Address::SevenBit(address) => (0b000, u32::from(address)),
+            Address::TenBit(address) => (0b001, u32::from(address)),
+        };
+        let stall = if config.clock_stretching {
+            SCFGR1_ADRSTALL | SCFGR1_RXSTALL | SCFGR1_TXDSTALL
+        } else {
+            0
+        };
+        let gcen = if config.general_call { SCFGR1_GCEN } else { 0 };
+
+        lpi2c
+            .scfgr1
+            .write(|w| unsafe { w.bits((addrcfg << SCFGR1_ADDRCFG_SHIFT) | stall | gcen) });
+        lpi2c.samr.write(|w| unsafe { w.bits(address << 1) });
+
+        lpi2c.scr.write(|w| unsafe { w.bits(SCR_SEN | SCR_FILTEN) });
+
+        Ok(Slave {
+            lpi2c: lpi2c,
+            config: config,
+        })
+    }
+
+    /// Return the next event, if any
+    ///
+    /// Call this until it returns `None` when polling, or from the LPI2C0 slave interrupt.
+    pub fn event(&self) -> Option<SlaveEvent> {
+        let ssr = self.lpi2c.ssr.read().bits();
+
+        if ssr & (SSR_BEF | SSR_FEF) != 0 {
+            self.lpi2c
+                .ssr
+                .write(|w| unsafe { w.bits(SSR_BEF | SSR_FEF) });
+            Some(SlaveEvent::Error)
+        } else if ssr & SSR_AVF != 0 {
+            // Reading the address clears the flag, bit 0 is the read/write bit
+            let sasr = self.lpi2c.sasr.read().bits();
+            Some(SlaveEvent::AddressMatch {
+                read: sasr & 1 != 0,
+            })
+        } else if ssr & SSR_RDF != 0 {
+            Some(SlaveEvent::Received(self.lpi2c.srdr.read().bits() as u8))
+        } else if ssr & SSR_TDF != 0 {
+            Some(SlaveEvent::TransmitRequest)
+        } else if ssr & SSR_RSF != 0 {
+            self.lpi2c.ssr.write(|w| unsafe { w.bits(SSR_RSF) });
+            Some(SlaveEvent::RepeatedStart)
+        } else if ssr & SSR_SDF != 0 {
+            self.lpi2c.ssr.write(|w| unsafe { w.bits(SSR_SDF) });
+            Some(SlaveEvent::Stop)
+        } else {
+            None
+        }
+    }
+
+    /// Give the master a byte to read, after `SlaveEvent::TransmitRequest`
+    pub fn transmit(&self, byte: u8) {
+        self.lpi2c
+            .stdr
+            .write(|w| unsafe { w.bits(u32::from(byte)) });
+    }
+
+    /// Enable or disable the slave interrupt for all events
+    pub fn set_interrupt(&self, enable: bool) {
+        let events = SSR_TDF | SSR_RDF | SSR_AVF | SSR_RSF | SSR_SDF | SSR_BEF | SSR_FEF;
+        self.lpi2c
+            .sier
+            .write(|w| unsafe { w.bits(if enable { events } else { 0 }) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimum SCL low time of each speed in nanoseconds, from the I2C specification
+    fn low_min_ns(speed: Speed) -> u64 {
+        match speed {
+            Speed::Standard => 4_700,
+            Speed::Fast => 1_300,
+            Speed::FastPlus => 500,
+        }
+    }
+
+    /// Check the timing for `speed` against the register limits and the I2C specification
+    fn check(source: u32, speed: Speed) -> Timing {
+        let timing = find_timing(source, speed).unwrap();
+        let prescale = u32::from(timing.prescale);
+        let clklo = u32::from(timing.clklo);
+        let clkhi = u32::from(timing.clkhi);
+        assert!(prescale < 8);
+        assert!(clklo >= CLKLO_MIN && clklo <= CLK_MAX);
+        assert!(clkhi >= 1 && clkhi <= CLK_MAX);
+
+        let latency = 2 >> prescale;
+        let period = (clklo + clkhi + 2 + latency) << prescale;
+        let scl = source / period;
+        assert!(scl <= speed.freq(), "{} Hz above {:?}", scl, speed);
+        assert!(scl >= speed.freq() * 9 / 10, "{} Hz below {:?}", scl, speed);
+
+        let low_ns = u64::from((clklo + 1) << prescale) * 1_000_000_000 / u64::from(source);
+        assert!(low_ns >= low_min_ns(speed));
+        timing
+    }
+
+    #[test]
+    fn timing_8mhz() {
+        for &speed in [Speed::Standard, Speed::Fast, Speed::FastPlus].iter() {
+            check(8_000_000, speed);
+        }
+        let timing = check(8_000_000, Speed::Standard);
+        assert_eq!((timing.prescale, timing.clklo, timing.clkhi), (0, 38, 38));
+        let timing = check(8_000_000, Speed::Fast);
+        assert_eq!((timing.prescale, timing.clklo, timing.clkhi), (0, 11, 5));
+    }
+
+    #[test]
+    fn timing_48mhz() {
+        for &speed in [Speed::Standard, Speed::Fast, Speed::FastPlus].iter() {
+            check(48_000_000, speed);
+        }
+        let timing = check(48_000_000, Speed::Standard);
+        assert_eq!((timing.prescale, timing.clklo, timing.clkhi), (2, 59, 59));
+        let timing = check(48_000_000, Speed::FastPlus);
+        assert_eq!((timing.prescale, timing.clklo, timing.clkhi), (0, 30, 14));
+    }
+
+    #[test]
+    fn timing_out_of_range() {
+        // Too few cycles for the minimum CLKLO
+        assert_eq!(
+            find_timing(1_000_000, Speed::Fast),
+            Err(Error::UnsatisfiableBaud)
+        );
+        assert_eq!(
+            find_timing(2_000_000, Speed::FastPlus),
+            Err(Error::UnsatisfiableBaud)
+        );
+        // Too many cycles even with the largest prescaler
+        assert_eq!(
+            find_timing(u32::max_value(), Speed::Standard),
+            Err(Error::UnsatisfiableBaud)
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! The Low Power Interrupt Timer (LPIT)
+//!
+//! The LPIT has 4 channels counting down from a reload value, clocked by the LPIT0 PCC
+//! functional clock.
+//!
+//! ```rust
+//! let pcc_lpit0: pcc::Lpit0 = pcc.enable_with_source(pcc::ClockSource::Sircdiv2).unwrap();
+//! let lpit = lpit::Lpit::init(&p.LPIT0, &pcc_lpit0, &spc).unwrap();
+//! lpit.start_periodic(lpit::Channel::Ch0, 1000, true).unwrap();
+//! ```
+
+#![allow(dead_code)]
+
+use cortex_m;
+use s32k144;
+
+use crate::{pcc, spc};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The LPIT0 functional clock is not running
+    NoClock,
+
+    /// The requested frequency or period can't be reached from the functional clock
+    FrequencyOutOfRange,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Channel {
+    Ch0,
+    Ch1,
+    Ch2,
+    Ch3,
+}
+
+impl Channel {
+    fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// Operation mode of a channel
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    /// 32-bit counter, reloaded when it reaches zero
+    Periodic,
+
+    /// The lower and upper 16 bits of the reload value are used as two 16-bit counters
+    DualPeriodic,
+
+    /// 32-bit counter decremented on each trigger instead of the clock
+    TriggerAccumulator,
+}
+
+impl Mode {
+    fn bits(self) -> u32 {
+        match self {
+            Mode::Periodic => 0b00,
+            Mode::DualPeriodic => 0b01,
+            Mode::TriggerAccumulator => 0b10,
+        }
+    }
+}
+
+/// Trigger used by a channel
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Trigger {
+    /// The LPIT trigger input from TRGMUX
+    External,
+
+    /// The trigger output of a channel
+    Internal(Channel),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ChannelConfig {
+    pub mode: Mode,
+
+    /// Value loaded into the counter when it expires, the channel expires every `reload + 1` counts
+    pub reload: u32,
+
+    /// Generate an interrupt when the channel expires
+    pub interrupt: bool,
+
+    /// Decrement only when the previous channel expires, not used by channel 0
+    pub chain: bool,
+
+    pub trigger: Trigger,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        ChannelConfig {
+            mode: Mode::Periodic,
+            reload: 0xFFFF_FFFF,
+            interrupt: false,
+            chain: false,
+            trigger: Trigger::External,
+        }
+    }
+}
+
+/// Return the reload value of a channel expiring with `frequency` Hz from a `source` Hz clock
+pub fn reload_for_frequency(source: u32, frequency: u32) -> Result<u32, Error> {
+    if frequency == 0 || frequency > source {
+        return Err(Error::FrequencyOutOfRange);
+    }
+    let counts = (u64::from(source) + u64::from(frequency / 2)) / u64::from(frequency);
+    Ok((counts - 1) as u32)
+}
+
+/// Return the reload value of a channel expiring every `period_us` microseconds from a `source` Hz clock
+pub fn reload_for_period_us(source: u32, period_us: u32) -> Result<u32, Error> {
+    let counts = u64::from(source) * u64::from(period_us) / 1_000_000;
+    if counts == 0 || counts > 0x1_0000_0000 {
+        return Err(Error::FrequencyOutOfRange);
+    }
+    Ok((counts - 1) as u32)
+}
+
+/// Run `$body` with `$reg` bound to the register of `$channel`
+macro_rules! with_channel_reg {
+    ($lpit:expr, $channel:expr, [$r0:ident, $r1:ident, $r2:ident, $r3:ident], |$reg:ident| $body:expr) => {
+        match $channel {
+            Channel::Ch0 => {
+                let $reg = &$lpit.$r0;
+                $body
+            }
+            Channel::Ch1 => {
+                let $reg = &$lpit.$r1;
+                $body
+            }
+            Channel::Ch2 => {
+                let $reg = &$lpit.$r2;
+                $body
+            }
+            Channel::Ch3 => {
+                let $reg = &$lpit.$r3;
+                $body
+            }
+        }
+    };
+}
+
+pub struct Lpit<'a> {
+    lpit: &'a s32k144::lpit0::RegisterBlock,
+    pcc_lpit0: &'a pcc::Lpit0<'a>,
+    freq: u32,
+}
+
+impl<'a> Lpit<'a> {
+    /// Enable the LPIT module with all channels stopped
+    pub fn init(
+        lpit: &'a s32k144::lpit0::RegisterBlock,
+        pcc_lpit0: &'a pcc::Lpit0,
+        spc: &spc::Spc,
+    ) -> Result<Self, Error> {
+        let freq = pcc_lpit0.functional_clock_freq(spc).ok_or(Error::NoClock)?;
+
+        lpit.mcr
+            .write(|w| w.m_cen().set_bit().dbg_en().set_bit().doze_en().set_bit());
+
+        // The timer registers must not be accessed until 4 functional clock cycles after M_CEN
+        let cycles = (4 * u64::from(spc.core_freq()) + u64::from(freq) - 1) / u64::from(freq);
+        cortex_m::asm::delay(cycles as u32);
+
+        lpit.clrten.write(|w| unsafe { w.bits(0b1111) });
+        lpit.msr.write(|w| unsafe { w.bits(0b1111) });
+
+        Ok(Lpit {
+            lpit: lpit,
+            pcc_lpit0: pcc_lpit0,
+            freq: freq,
+        })
+    }
+
+    /// Return the frequency the counters are clocked with
+    pub fn freq(&self) -> u32 {
+        self.freq
+    }
+
+    /// Configure a channel, the channel is stopped until `start` is called
+    pub fn configure(&self, channel: Channel, config: &ChannelConfig) {
+        self.stop(channel);
+
+        let (trg_src, trg_sel) = match config.trigger {
+            Trigger::External => (0, 0),
+            Trigger::Internal(source) => (1, source as u32),
+        };
+        let value = (trg_sel << 24)
+            | (trg_src << 23)
+            | (config.mode.bits() << 2)
+            | ((config.chain as u32) << 1);
+
+        let reload = config.reload;
+        with_channel_reg!(self.lpit, channel, [tval0, tval1, tval2, tval3], |tval| {
+            tval.write(|w| unsafe { w.bits(reload) })
+        });
+        with_channel_reg!(
+            self.lpit,
+            channel,
+            [tctrl0, tctrl1, tctrl2, tctrl3],
+            |tctrl| { tctrl.write(|w| unsafe { w.bits(value) }) }
+        );
+
+        self.set_interrupt(channel, config.interrupt);
+    }
+
+    /// Configure a channel as a periodic timer expiring with `frequency` Hz and start it
+    pub fn start_periodic(
+        &self,
+        channel: Channel,
+        frequency: u32,
+        interrupt: bool,
+    ) -> Result<(), Error> {
+        let config = ChannelConfig {
+            reload: reload_for_frequency(self.freq, frequency)?,
+            interrupt: interrupt,
+            ..Default::default()
+        };
+        self.configure(channel, &config);
+        self.start(channel);
+        Ok(())
+    }
+
+    /// Configure a channel to expire after `count` triggers and start it
+    pub fn start_trigger_accumulator(
+        &self,
+        channel: Channel,
+        count: u32,
+        trigger: Trigger,
+        interrupt: bool,
+    ) {
+        let config = ChannelConfig {
+            mode: Mode::TriggerAccumulator,
+            reload: count.saturating_sub(1),
+            interrupt: interrupt,
+            trigger: trigger,
+            ..Default::default()
+        };
+        self.configure(channel, &config);
+        self.start(channel);
+    }
+
+    /// Change the reload value, it's used from the next time the channel expires
+    pub fn set_reload(&self, channel: Channel, reload: u32) {
+        with_channel_reg!(self.lpit, channel, [tval0, tval1, tval2, tval3], |tval| {
+            tval.write(|w| unsafe { w.bits(reload) })
+        });
+    }
+
+    pub fn start(&self, channel: Channel) {
+        self.lpit
+            .setten
+            .write(|w| unsafe { w.bits(channel.mask()) });
+    }
+
+    pub fn stop(&self, channel: Channel) {
+        self.lpit
+            .clrten
+            .write(|w| unsafe { w.bits(channel.mask()) });
+    }
+
+    /// Enable or disable the interrupt of a channel
+    ///
+    /// Each channel has its own interrupt vector, `LPIT0_Ch0` to `LPIT0_Ch3`.
+    pub fn set_interrupt(&self, channel: Channel, enable: bool) {
+        let mask = channel.mask();
+        self.lpit.mier.modify(|r, w| unsafe {
+            if enable {
+                w.bits(r.bits() | mask)
+            } else {
+                w.bits(r.bits() & !mask)
+            }
+        });
+    }
+
+    /// Return true if the channel has expired since the flag was cleared
+    pub fn has_expired(&self, channel: Channel) -> bool {
+        self.lpit.msr.read().bits() & channel.mask() != 0
+    }
+
+    /// Clear the expired flag, this also clears the interrupt request
+    pub fn clear_expired(&self, channel: Channel) {
+        self.lpit.msr.write(|w| unsafe { w.bits(channel.mask()) });
+    }
+
+    /// Return the current value of the counter
+    pub fn current_value(&self, channel: Channel) -> u32 {
+        with_channel_reg!(self.lpit, channel, [cval0, cval1, cval2, cval3], |cval| {
+            cval.read().bits()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_from_frequency() {
+        assert_eq!(reload_for_frequency(48_000_000, 1_000), Ok(47_999));
+        // Rounded to the closest count
+        assert_eq!(reload_for_frequency(8_000_000, 3), Ok(2_666_666));
+        assert_eq!(reload_for_frequency(8_000_000, 8_000_000), Ok(0));
+    }
+
+    #[test]
+    fn reload_from_frequency_out_of_range() {
+        assert_eq!(
+            reload_for_frequency(8_000_000, 0),
+            Err(Error::FrequencyOutOfRange)
+        );
+        assert_eq!(
+            reload_for_frequency(8_000_000, 8_000_001),
+            Err(Error::FrequencyOutOfRange)
+        );
+    }
+
+    #[test]
+    fn reload_from_frequency_does_not_overflow() {
+        assert_eq!(
+            reload_for_frequency(u32::max_value(), 2),
+            Ok(u32::max_value() / 2)
+        );
+        assert_eq!(
+            reload_for_frequency(u32::max_value(), 1),
+            Ok(u32::max_value() - 1)
+        );
+    }
+
+    #[test]
+    fn reload_from_period() {
+        assert_eq!(reload_for_period_us(48_000_000, 1_000), Ok(47_999));
+        assert_eq!(reload_for_period_us(8_000_000, 1), Ok(7));
+    }
+
+    #[test]
+    fn reload_from_period_out_of_range() {
+        assert_eq!(
+            reload_for_period_us(8_000_000, 0),
+            Err(Error::FrequencyOutOfRange)
+        );
+        // Less than one count
+        assert_eq!(
+            reload_for_period_us(1_000, 500),
+            Err(Error::FrequencyOutOfRange)
+        );
+    }
+
+    #[test]
+    fn reload_from_period_limit() {
+        // 2^32 counts is the longest period
+        assert_eq!(
+            reload_for_period_us(2_000_000, 2_147_483_648),
+            Ok(u32::max_value())
+        );
+        assert_eq!(
+            reload_for_period_us(2_000_000, 2_147_483_649),
+            Err(Error::FrequencyOutOfRange)
+        );
+        assert_eq!(
+            reload_for_period_us(u32::max_value(), u32::max_value()),
+            Err(Error::FrequencyOutOfRange)
+        );
+    }
+}
@@ -0,0 +1,575 @@
+//! The Low Power Serial Peripheral Interfaces (LPSPI0 to LPSPI2)
+//!
+//! The SPI clock is divided from the LPSPI's PCC functional clock. All three instances have the
+//! same registers.
+//!
+//! ```rust
+//! let pcc_lpspi1: pcc::Lpspi1 = pcc.enable_with_source(pcc::ClockSource::Sircdiv2).unwrap();
+//! let config = lpspi::Config::default();
+//! let mut spi = lpspi::Lpspi::init(&p.LPSPI1, &pcc_lpspi1, &spc, config).unwrap();
+//! let mut frame = [0x12u8, 0x34];
+//! spi.transfer(&mut frame).unwrap();
+//! ```
+
+#![allow(dead_code)]
+
+use embedded_hal::blocking;
+use embedded_hal::spi::{Mode, Phase, Polarity, MODE_0};
+use s32k144::{self, lpspi0};
+
+use crate::{pcc, spc};
+
+/// Control register bits
+const CR_MEN: u32 = 1 << 0;
+const CR_RST: u32 = 1 << 1;
+const CR_DBGEN: u32 = 1 << 3;
+const CR_RTF: u32 = 1 << 8;
+const CR_RRF: u32 = 1 << 9;
+
+/// Status register bits
+const SR_TDF: u32 = 1 << 0;
+const SR_RDF: u32 = 1 << 1;
+const SR_WCF: u32 = 1 << 8;
+const SR_FCF: u32 = 1 << 9;
+const SR_TCF: u32 = 1 << 10;
+const SR_TEF: u32 = 1 << 11;
+const SR_REF: u32 = 1 << 12;
+const SR_MBF: u32 = 1 << 24;
+
+/// Configuration register 1 bits
+const CFGR1_MASTER: u32 = 1 << 0;
+const CFGR1_PCSPOL_SHIFT: u32 = 8;
+
+/// Transmit command register bits
+const TCR_CPOL: u32 = 1 << 31;
+const TCR_CPHA: u32 = 1 << 30;
+const TCR_PRESCALE_SHIFT: u32 = 27;
+const TCR_PCS_SHIFT: u32 = 24;
+const TCR_LSBF: u32 = 1 << 23;
+const TCR_CONT: u32 = 1 << 21;
+
+/// Receive status register bits
+const RSR_RXEMPTY: u32 = 1 << 1;
+
+/// Frame sizes supported by TCR.FRAMESZ
+const FRAME_SIZE_MIN: u16 = 8;
+const FRAME_SIZE_MAX: u16 = 4096;
+
+/// Number of times a status flag is checked before giving up
+const STATUS_CHECKS: u32 = 1_000_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The functional clock of the LPSPI is not running
+    NoClock,
+
+    /// The register block is not the LPSPI of the clock guard
+    WrongInstance,
+
+    /// The baud rate can't be reached from the functional clock
+    UnsatisfiableBaud,
+
+    /// The frame size is outside 8 to 4096 bits
+    InvalidFrameSize,
+
+    /// The receive FIFO overflowed and data was lost
+    ReceiveOverflow,
+
+    /// The transmit FIFO underflowed in slave mode
+    TransmitUnderflow,
+
+    /// The transfer didn't complete in time, e.g. when no master clocks a slave
+    Timeout,
+}
+
+/// Master or slave operation
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Role {
+    Master,
+    Slave,
+}
+
+/// Peripheral chip select
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Pcs {
+    Pcs0,
+    Pcs1,
+    Pcs2,
+    Pcs3,
+}
+
+/// Status flags that can generate an interrupt
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Event {
+    /// The transmit FIFO is at or below the watermark
+    TransmitData,
+
+    /// The receive FIFO is above the watermark
+    ReceiveData,
+
+    /// A word has been transferred
+    WordComplete,
+
+    /// A frame has been transferred
+    FrameComplete,
+
+    /// All data has been transferred and the transmit FIFO is empty
+    TransferComplete,
+
+    /// The transmit FIFO underflowed
+    TransmitError,
+
+    /// The receive FIFO overflowed
+    ReceiveError,
+}
+
+impl Event {
+    fn mask(self) -> u32 {
+        match self {
+            Event::TransmitData => SR_TDF,
+            Event::ReceiveData => SR_RDF,
+            Event::WordComplete => SR_WCF,
+            Event::FrameComplete => SR_FCF,
+            Event::TransferComplete => SR_TCF,
+            Event::TransmitError => SR_TEF,
+            Event::ReceiveError => SR_REF,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Config {
+    pub role: Role,
+
+    /// SCK frequency in master mode, the closest frequency not above it is used
+    pub baudrate: u32,
+
+    /// Clock polarity and phase
+    pub mode: Mode,
+
+    /// Bits per frame, from 8 to 4096, frames longer than 32 bits are split over several words
+    pub frame_size: u16,
+
+    pub lsb_first: bool,
+
+    pub pcs: Pcs,
+
+    /// The chip select is active high instead of active low
+    pub pcs_active_high: bool,
+
+    /// Keep the chip select asserted between the words of a `transfer` or `write`
+    pub continuous: bool,
+
+    /// The transmit data flag is set when the transmit FIFO holds this many words or fewer
+    pub tx_watermark: u8,
+
+    /// The receive data flag is set when the receive FIFO holds more than this many words
+    pub rx_watermark: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            role: Role::Master,
+            baudrate: 1_000_000,
+            mode: MODE_0,
+            frame_size: 8,
+            lsb_first: false,
+            pcs: Pcs::Pcs0,
+            pcs_active_high: false,
+            continuous: true,
+            tx_watermark: 0,
+            rx_watermark: 0,
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// The PCC clock guards of the LPSPIs, `pcc::Lpspi0` to `pcc::Lpspi2`
+pub trait LpspiClock: sealed::Sealed {
+    /// Return the register block of the LPSPI the guard belongs to
+    fn ptr() -> *const lpspi0::RegisterBlock;
+
+    /// Return the frequency of the functional clock if running
+    fn functional_clock_freq(&self, spc: &spc::Spc) -> Option<u32>;
+}
+
+macro_rules! lpspi_clock {
+    ($($Guard:ident: $LPSPI:ident,)+) => {
+        $(
+            impl<'a> sealed::Sealed for pcc::$Guard<'a> {}
+
+            impl<'a> LpspiClock for pcc::$Guard<'a> {
+                fn ptr() -> *const lpspi0::RegisterBlock {
+                    s32k144::$LPSPI::ptr()
+                }
+
+                fn functional_clock_freq(&self, spc: &spc::Spc) -> Option<u32> {
+                    pcc::$Guard::functional_clock_freq(self, spc)
+                }
+            }
+        )+
+    };
+}
+
+lpspi_clock! {
+    Lpspi0: LPSPI0,
+    Lpspi1: LPSPI1,
+    Lpspi2: LPSPI2,
+}
+
+/// Find the prescaler and SCK divider giving the fastest SCK not above `baud`
+///
+/// The SCK frequency is `source / (2^prescale * (sckdiv + 2))`.
+fn find_baud_div(source: u32, baud: u32) -> Result<(u8, u8), Error> {
+    const PRESCALE_MAX: u32 = 7;
+    const SCKDIV_MAX: u32 = 255;
+
+    if baud == 0 {
+        return Err(Error::UnsatisfiableBaud);
+    }
+
+    let mut best: Option<(u32, u8, u8)> = None;
+    for prescale in 0..=PRESCALE_MAX {
+        let divided = source >> prescale;
+        let div = divided / baud + (divided % baud != 0) as u32;
+        let div = if div < 2 { 2 } else { div };
+        if div - 2 > SCKDIV_MAX {
+            continue;
+        }
+
+        let actual = divided / div;
+        let better = match best {
+            Some((best_baud, _, _)) => actual > best_baud,
+            None => true,
+        };
+        if better {
+            best = Some((actual, prescale as u8, (div - 2) as u8));
+        }
+    }
+
+    match best {
+        Some((_, prescale, sckdiv)) => Ok((prescale, sckdiv)),
+        None => Err(Error::UnsatisfiableBaud),
+    }
+}
+
+pub struct Lpspi<'a> {
+    lpspi: &'a lpspi0::RegisterBlock,
+    config: Config,
+    tcr: u32,
+}
+
+impl<'a> Lpspi<'a> {
+    /// Configure and enable the LPSPI
+    ///
+    /// The SPI clock is divided from the functional clock of `pcc_lpspi`, which must be enabled
+    /// with a source. `lpspi` must be the LPSPI of the clock guard.
+    pub fn init<P: LpspiClock>(
+        lpspi: &'a lpspi0::RegisterBlock,
+        pcc_lpspi: &'a P,
+        spc: &spc::Spc,
+        config: Config,
+    ) -> Result<Lpspi<'a>, Error> {
+        if lpspi as *const lpspi0::RegisterBlock != P::ptr() {
+            return Err(Error::WrongInstance);
+        }
+        let source_frequency = pcc_lpspi.functional_clock_freq(spc).ok_or(Error::NoClock)?;
+        if config.frame_size < FRAME_SIZE_MIN || config.frame_size > FRAME_SIZE_MAX {
+            return Err(Error::InvalidFrameSize);
+        }
+        let (prescale, sckdiv) = match config.role {
+            Role::Master => find_baud_div(source_frequency, config.baudrate)?,
+            Role::Slave => (0, 0),
+        };
+
+        // Reset the module, this also disables it
+        lpspi.cr.write(|w| unsafe { w.bits(CR_RST) });
+        lpspi.cr.write(|w| unsafe { w.bits(0) });
+
+        let master = if config.role == Role::Master {
+            CFGR1_MASTER
+        } else {
+            0
+        };
+        let pcspol = if config.pcs_active_high {
+            1 << (CFGR1_PCSPOL_SHIFT + config.pcs as u32)
+        } else {
+            0
+        };
+        lpspi.cfgr1.write(|w| unsafe { w.bits(master | pcspol) });
+
+        // Use half a SCK period for the delays around the chip select and between transfers
+        let delay = u32::from(sckdiv / 2);
+        lpspi.ccr.write(|w| unsafe {
+            w.bits((delay << 24) | (delay << 16) | (delay << 8) | u32::from(sckdiv))
+        });
+
+        lpspi.fcr.write(|w| unsafe {
+            w.bits((u32::from(config.rx_watermark) << 16) | u32::from(config.tx_watermark))
+        });
+
+        let mut tcr = (u32::from(prescale) << TCR_PRESCALE_SHIFT)
+            | ((config.pcs as u32) << TCR_PCS_SHIFT)
+            | u32::from(config.frame_size - 1);
+        if config.mode.polarity == Polarity::IdleHigh {
+            tcr |= TCR_CPOL;
+        }
+        if config.mode.phase == Phase::CaptureOnSecondTransition {
+            tcr |= TCR_CPHA;
+        }
+        if config.lsb_first {
+            tcr |= TCR_LSBF;
+        }
+
+        lpspi.cr.write(|w| unsafe { w.bits(CR_MEN | CR_DBGEN) });
+        lpspi.tcr.write(|w| unsafe { w.bits(tcr) });
+
+        Ok(Lpspi {
+            lpspi: lpspi,
+            config: config,
+            tcr: tcr,
+        })
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Return the number of words the transmit and receive FIFOs hold
+    pub fn fifo_size(&self) -> (u32, u32) {
+        let param = self.lpspi.param.read().bits();
+        (1 << (param & 0xFF), 1 << ((param >> 8) & 0xFF))
+    }
+
+    /// Select another peripheral chip select for the next transfers
+    pub fn select(&mut self, pcs: Pcs) {
+        self.tcr = (self.tcr & !(0b11 << TCR_PCS_SHIFT)) | ((pcs as u32) << TCR_PCS_SHIFT);
+        self.config.pcs = pcs;
+        let tcr = self.tcr;
+        self.lpspi.tcr.write(|w| unsafe { w.bits(tcr) });
+    }
+
+    /// Enable the interrupt request of a status flag
+    pub fn listen(&self, event: Event) {
+        self.lpspi
+            .ier
+            .modify(|r, w| unsafe { w.bits(r.bits() | event.mask()) });
+    }
+
+    /// Disable the interrupt request of a status flag
+    pub fn unlisten(&self, event: Event) {
+        self.lpspi
+            .ier
+            .modify(|r, w| unsafe { w.bits(r.bits() & !event.mask()) });
+    }
+
+    /// Return true if the status flag is set
+    pub fn is_pending(&self, event: Event) -> bool {
+        self.lpspi.sr.read().bits() & event.mask() != 0
+    }
+
+    /// Clear a status flag, the FIFO flags are cleared by writing and reading the FIFOs instead
+    pub fn clear(&self, event: Event) {
+        self.lpspi.sr.write(|w| unsafe { w.bits(event.mask()) });
+    }
+
+    /// Return true while a transfer is in progress
+    pub fn is_busy(&self) -> bool {
+        self.lpspi.sr.read().bits() & SR_MBF != 0
+    }
+
+    /// Discard everything in the transmit and receive FIFOs
+    pub fn flush(&self) {
+        self.lpspi
+            .cr
+            .modify(|r, w| unsafe { w.bits(r.bits() | CR_RTF | CR_RRF) });
+    }
+
+    /// Start a command, keeping the chip select asserted if configured as continuous
+    fn begin(&self) {
+        if self.config.continuous && self.config.role == Role::Master {
+            let tcr = self.tcr | TCR_CONT;
+            self.lpspi.tcr.write(|w| unsafe { w.bits(tcr) });
+        }
+    }
+
+    /// End a continuous command, deasserting the chip select
+    fn end(&self) {
+        if self.config.continuous && self.config.role == Role::Master {
+            let tcr = self.tcr;
+            self.lpspi.tcr.write(|w| unsafe { w.bits(tcr) });
+        }
+    }
+
+    fn check_errors(&self) -> Result<(), Error> {
+        let sr = self.lpspi.sr.read().bits();
+        if sr & SR_REF != 0 {
+            self.lpspi.sr.write(|w| unsafe { w.bits(SR_REF) });
+            Err(Error::ReceiveOverflow)
+        } else if sr & SR_TEF != 0 {
+            self.lpspi.sr.write(|w| unsafe { w.bits(SR_TEF) });
+            Err(Error::TransmitUnderflow)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write a word to the transmit FIFO, waiting for room
+    fn write_word(&self, word: u32) -> Result<(), Error> {
+        let mut checks = 0;
+        while self.lpspi.sr.read().bits() & SR_TDF == 0 {
+            checks += 1;
+            if checks >= STATUS_CHECKS {
+                return Err(Error::Timeout);
+            }
+        }
+        self.lpspi.tdr.write(|w| unsafe { w.bits(word) });
+        Ok(())
+    }
+
+    /// Read a word from the receive FIFO, waiting for it to arrive
+    fn read_word(&self) -> Result<u32, Error> {
+        let mut checks = 0;
+        while self.lpspi.rsr.read().bits() & RSR_RXEMPTY != 0 {
+            self.check_errors()?;
+            checks += 1;
+            if checks >= STATUS_CHECKS {
+                return Err(Error::Timeout);
+            }
+        }
+        Ok(self.lpspi.rdr.read().bits())
+    }
+
+    /// Transfer the words one at a time, replacing each with the received word
+    fn transfer_words<W: Copy + Into<u32>, F: Fn(u32) -> W>(
+        &self,
+        words: &mut [W],
+        from_word: F,
+    ) -> Result<(), Error> {
+        self.begin();
+        let result = words.iter_mut().try_for_each(|word| {
+            self.write_word((*word).into())?;
+            *word = from_word(self.read_word()?);
+            Ok(())
+        });
+        self.end();
+        result
+    }
+
+    /// Write the words, discarding what is received
+    fn write_words<W: Copy + Into<u32>>(&self, words: &[W]) -> Result<(), Error> {
+        self.begin();
+        let result = words.iter().try_for_each(|&word| {
+            self.write_word(word.into())?;
+            self.read_word().map(|_| ())
+        });
+        self.end();
+        result
+    }
+}
+
+impl<'a> blocking::spi::Transfer<u8> for Lpspi<'a> {
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+        self.transfer_words(words, |word| word as u8)?;
+        Ok(words)
+    }
+}
+
+impl<'a> blocking::spi::Write<u8> for Lpspi<'a> {
+    type Error = Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Error> {
+        self.write_words(words)
+    }
+}
+
+impl<'a> blocking::spi::Transfer<u16> for Lpspi<'a> {
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u16]) -> Result<&'w [u16], Error> {
+        self.transfer_words(words, |word| word as u16)?;
+        Ok(words)
+    }
+}
+
+impl<'a> blocking::spi::Write<u16> for Lpspi<'a> {
+    type Error = Error;
+
+    fn write(&mut self, words: &[u16]) -> Result<(), Error> {
+        self.write_words(words)
+    }
+}
+
+impl<'a> blocking::spi::Transfer<u32> for Lpspi<'a> {
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u32]) -> Result<&'w [u32], Error> {
+        self.transfer_words(words, |word| word)?;
+        Ok(words)
+    }
+}
+
+impl<'a> blocking::spi::Write<u32> for Lpspi<'a> {
+    type Error = Error;
+
+    fn write(&mut self, words: &[u32]) -> Result<(), Error> {
+        self.write_words(words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sck_freq(source: u32, (prescale, sckdiv): (u8, u8)) -> u32 {
+        (source >> prescale) / (u32::from(sckdiv) + 2)
+    }
+
+    #[test]
+    fn exact_baud() {
+        assert_eq!(find_baud_div(8_000_000, 1_000_000), Ok((0, 6)));
+        assert_eq!(find_baud_div(48_000_000, 400_000), Ok((0, 118)));
+    }
+
+    #[test]
+    fn never_above_baud() {
+        assert_eq!(find_baud_div(8_000_000, 3_000_000), Ok((0, 1)));
+        for &baud in [2_000, 33_333, 125_000, 999_999, 2_500_000, 7_000_000].iter() {
+            let sck = sck_freq(48_000_000, find_baud_div(48_000_000, baud).unwrap());
+            assert!(sck <= baud, "{} Hz above {} Hz", sck, baud);
+        }
+    }
+
+    #[test]
+    fn fastest_is_half_the_source() {
+        assert_eq!(find_baud_div(48_000_000, 100_000_000), Ok((0, 0)));
+    }
+
+    #[test]
+    fn unreachable_baud() {
+        assert_eq!(find_baud_div(8_000_000, 0), Err(Error::UnsatisfiableBaud));
+        // The slowest SCK is 48 MHz / (128 * 257), about 1459 Hz
+        assert_eq!(
+            find_baud_div(48_000_000, 1_000),
+            Err(Error::UnsatisfiableBaud)
+        );
+    }
+
+    #[test]
+    fn large_values_dont_overflow() {
+        assert_eq!(
+            find_baud_div(u32::max_value(), u32::max_value()),
+            Ok((0, 0))
+        );
+        assert_eq!(
+            find_baud_div(u32::max_value(), u32::max_value() - 1),
+            Ok((0, 0))
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! The Low Power Timer (LPTMR)
+//!
+//! The LPTMR keeps counting in all stop modes as long as its clock runs, and its interrupt can be
+//! used to wake up from `spc::Spc::sleep`. The 1 kHz LPO clock always runs, while SIRCDIV2 must be
+//! kept running in stop modes with `spc::Spc::set_sirc_in_stop`.
+//!
+//! ```rust
+//! let lptmr = lptmr::Lptmr::init(&p.LPTMR0, &pcc_lptmr0, &spc, lptmr::Clock::Lpo1k).unwrap();
+//! lptmr.start_timer(500_000, true).unwrap();
+//! spc.sleep(spc::StopMode::VeryLowPowerStop, &mut cp.SCB, &mut cp.NVIC, Interrupt::LPTMR0)
+//!     .unwrap();
+//! ```
+
+#![allow(dead_code)]
+
+use s32k144;
+
+use crate::{pcc, spc};
+
+/// Frequency of the LPO1K clock
+const LPO1K_FREQ: u32 = 1_000;
+
+/// Highest prescaler setting, dividing by 2^16
+const PRESCALER_MAX: u8 = 15;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The selected clock is not running
+    NoClock,
+
+    /// The requested period can't be counted with the selected clock
+    PeriodOutOfRange,
+
+    /// The prescaler or glitch filter setting is out of range
+    InvalidPrescaler,
+}
+
+/// Clock of the prescaler and glitch filter
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Clock {
+    /// `SIRCDIV2_CLK`
+    Sircdiv2,
+
+    /// The 1 kHz output of the LPO
+    Lpo1k,
+
+    /// The LPTMR0 PCC functional clock, e.g. `SOSCDIV2_CLK`
+    Pcc,
+}
+
+impl Clock {
+    fn pcs(self) -> u8 {
+        match self {
+            Clock::Sircdiv2 => 0b00,
+            Clock::Lpo1k => 0b01,
+            Clock::Pcc => 0b11,
+        }
+    }
+}
+
+/// Input counted in pulse counter mode
+///
+/// See the reference manual for what is connected to each input, e.g. CMP0 or the LPTMR_ALTn pins.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PulseInput {
+    Input0,
+    Input1,
+    Input2,
+    Input3,
+}
+
+/// Edge counted in pulse counter mode
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Polarity {
+    Rising,
+    Falling,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    /// Count the clock, divided by `2^(prescaler + 1)` or not divided if `None`
+    TimeCounter { prescaler: Option<u8> },
+
+    /// Count edges on an input
+    ///
+    /// With `glitch_filter` set to `Some(n)`, with `n` from 1 to 15, a change of the input is only
+    /// recognized after `2^n` edges of the clock.
+    PulseCounter {
+        input: PulseInput,
+        polarity: Polarity,
+        glitch_filter: Option<u8>,
+    },
+}
+
+/// Return the prescaler setting and compare value for a timer expiring every `period_us` µs
+///
+/// The smallest prescaler is used, for the best resolution.
+pub fn find_compare(source: u32, period_us: u32) -> Result<(Option<u8>, u16), Error> {
+    let ticks = u64::from(source) * u64::from(period_us) / 1_000_000;
+    if ticks == 0 {
+        return Err(Error::PeriodOutOfRange);
+    }
+    if ticks <= 0x1_0000 {
+        return Ok((None, (ticks - 1) as u16));
+    }
+
+    for prescaler in 0..=PRESCALER_MAX {
+        let divided = ticks >> (prescaler + 1);
+        if divided == 0 {
+            break;
+        }
+        if divided <= 0x1_0000 {
+            return Ok((Some(prescaler), (divided - 1) as u16));
+        }
+    }
+    Err(Error::PeriodOutOfRange)
+}
+
+pub struct Lptmr<'a> {
+    lptmr: &'a s32k144::lptmr0::RegisterBlock,
+    pcc_lptmr0: &'a pcc::Lptmr0<'a>,
+    clock: Clock,
+    freq: u32,
+}
+
+impl<'a> Lptmr<'a> {
+    /// Stop the timer and select its clock
+    pub fn init(
+        lptmr: &'a s32k144::lptmr0::RegisterBlock,
+        pcc_lptmr0: &'a pcc::Lptmr0,
+        spc: &spc::Spc,
+        clock: Clock,
+    ) -> Result<Self, Error> {
+        let freq = match clock {
+            Clock::Sircdiv2 => spc.sircdiv2_freq(),
+            Clock::Lpo1k => Some(LPO1K_FREQ),
+            Clock::Pcc => pcc_lptmr0.functional_clock_freq(spc),
+        }
+        .ok_or(Error::NoClock)?;
+
+        lptmr.csr.write(|w| unsafe { w.bits(0) });
+        // Clear a pending compare flag, it's write 1 to clear
+        lptmr.csr.write(|w| w.tcf().set_bit());
+
+        Ok(Lptmr {
+            lptmr: lptmr,
+            pcc_lptmr0: pcc_lptmr0,
+            clock: clock,
+            freq: freq,
+        })
+    }
+
+    /// Return the frequency of the clock, before the prescaler
+    pub fn freq(&self) -> u32 {
+        self.freq
+    }
+
+    /// Configure the mode and compare value and start the timer
+    ///
+    /// The compare flag is set when the counter equals `compare`, and the counter is reset at the
+    /// next increment unless `free_running` is set.
+    pub fn start(
+        &self,
+        mode: Mode,
+        compare: u16,
+        free_running: bool,
+        interrupt: bool,
+    ) -> Result<(), Error> {
+        let (tms, tps, tpp, prescaler) = match mode {
+            Mode::TimeCounter { prescaler } => (false, 0, false, prescaler),
+            Mode::PulseCounter {
+                input,
+                polarity,
+                glitch_filter,
+            } => {
+                if glitch_filter == Some(0) {
+                    return Err(Error::InvalidPrescaler);
+                }
+                (
+                    true,
+                    input as u8,
+                    polarity == Polarity::Falling,
+                    glitch_filter,
+                )
+            }
+        };
+        if prescaler.map_or(false, |prescaler| prescaler > PRESCALER_MAX) {
+            return Err(Error::InvalidPrescaler);
+        }
+
+        // The timer must be disabled while it's configured
+        self.stop();
+
+        let pcs = self.clock.pcs();
+        self.lptmr.psr.write(|w| unsafe {
+            w.pcs()
+                .bits(pcs)
+                .pbyp()
+                .bit(prescaler.is_none())
+                .prescale()
+                .bits(prescaler.unwrap_or(0))
+        });
+        self.lptmr
+            .cmr
+            .write(|w| unsafe { w.compare().bits(compare) });
+        self.lptmr.csr.write(|w| unsafe {
+            w.tms()
+                .bit(tms)
+                .tps()
+                .bits(tps)
+                .tpp()
+                .bit(tpp)
+                .tfc()
+                .bit(free_running)
+                .tie()
+                .bit(interrupt)
+                .tcf()
+                .set_bit()
+        });
+        self.lptmr.csr.modify(|_, w| w.ten().set_bit());
+
+        Ok(())
+    }
+
+    /// Start a timer setting the compare flag every `period_us` microseconds
+    pub fn start_timer(&self, period_us: u32, interrupt: bool) -> Result<(), Error> {
+        let (prescaler, compare) = find_compare(self.freq, period_us)?;
+        self.start(
+            Mode::TimeCounter {
+                prescaler: prescaler,
+            },
+            compare,
+            false,
+            interrupt,
+        )
+    }
+
+    /// Stop the timer, this resets the counter
+    pub fn stop(&self) {
+        self.lptmr
+            .csr
+            .modify(|_, w| w.ten().clear_bit().tcf().clear_bit());
+    }
+
+    /// Return the current value of the counter
+    pub fn counter(&self) -> u16 {
+        // The counter is latched by writing to it
+        self.lptmr.cnr.write(|w| unsafe { w.bits(0) });
+        self.lptmr.cnr.read().counter().bits()
+    }
+
+    /// Return true if the counter has reached the compare value since the flag was cleared
+    pub fn has_expired(&self) -> bool {
+        self.lptmr.csr.read().tcf().bit_is_set()
+    }
+
+    /// Clear the compare flag, this also clears the interrupt request
+    pub fn clear_expired(&self) {
+        self.lptmr.csr.modify(|_, w| w.tcf().set_bit());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_without_prescaler() {
+        assert_eq!(find_compare(LPO1K_FREQ, 1_000_000), Ok((None, 999)));
+        assert_eq!(find_compare(1_000_000, 1), Ok((None, 0)));
+        assert_eq!(find_compare(1_000_000, 0x1_0000), Ok((None, 0xFFFF)));
+    }
+
+    #[test]
+    fn compare_with_prescaler() {
+        // The first prescaler setting divides by 2
+        assert_eq!(find_compare(1_000_000, 0x1_0001), Ok((Some(0), 0x7FFF)));
+        assert_eq!(find_compare(8_000_000, 1_000_000), Ok((Some(6), 62_499)));
+    }
+
+    #[test]
+    fn compare_largest_prescaler() {
+        assert_eq!(
+            find_compare(1_000_000, u32::max_value()),
+            Ok((Some(PRESCALER_MAX), 0xFFFE))
+        );
+    }
+
+    #[test]
+    fn compare_out_of_range() {
+        assert_eq!(find_compare(1_000_000, 0), Err(Error::PeriodOutOfRange));
+        // Shorter than one count
+        assert_eq!(find_compare(LPO1K_FREQ, 500), Err(Error::PeriodOutOfRange));
+        // Longer than the largest prescaler can count
+        assert_eq!(
+            find_compare(8_000_000, u32::max_value()),
+            Err(Error::PeriodOutOfRange)
+        );
+    }
+}
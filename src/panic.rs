@@ -1,11 +1,13 @@
 //! With the panic handler being `#[inline(never)]` the symbol `rust_begin_unwind` will be
 //! available to place a breakpoint on to halt when a panic is happening.
+//!
+//! When panicking over serial, the red LED blinks SOS afterwards so a panic can be seen without
+//! a serial connection.
 
-use crate::{console, spc};
-use core::{
-    panic::PanicInfo,
-    sync::atomic::{self, Ordering},
-};
+use crate::{blink, console, spc};
+use core::panic::PanicInfo;
+#[cfg(feature = "panic-over-itm")]
+use core::sync::atomic::{self, Ordering};
 use cortex_m;
 use embedded_types::io::Write;
 use s32k144;
@@ -35,7 +37,7 @@ fn panic(info: &PanicInfo) -> ! {
         ..Default::default()
     };
 
-    cortex_m::interrupt::free(|_cs| unsafe {
+    let core_freq = cortex_m::interrupt::free(|_cs| unsafe {
         let pcc = &*s32k144::PCC::ptr();
         let portc = &*s32k144::PORTC::ptr();
         let portd = &*s32k144::PORTD::ptr();
@@ -48,20 +50,40 @@ fn panic(info: &PanicInfo) -> ! {
         portc.pcr9.modify(|_, w| w.mux()._000());
         portd.pcr14.modify(|_, w| w.mux()._000());
 
+        // The red LED on PTD15, active low
+        let ptd = &*s32k144::PTD::ptr();
+        portd.pcr15.modify(|_, w| w.mux()._001());
+        ptd.pddr.modify(|r, w| w.bits(r.bits() | (1 << 15)));
+
+        // Errors are ignored, a panic in here would never reach the blinking below
         let spc = spc::Spc::init(
             &*s32k144::SCG::ptr(),
             &*s32k144::SMC::ptr(),
             &*s32k144::PMC::ptr(),
             spc_config,
-        )
-        .unwrap();
-
-        let mut serial = console::LpuartConsole::init(&*s32k144::LPUART1::ptr(), &spc);
+        );
 
-        writeln!(serial, "{}", info).unwrap();
+        match spc {
+            Ok(spc) => {
+                let mut serial = console::LpuartConsole::init(&*s32k144::LPUART1::ptr(), &spc);
+                let _ = writeln!(serial, "{}", info);
+                spc.core_freq()
+            }
+            // The system is left running from FIRC, e.g. without a working crystal
+            Err(_) => spc::FIRC_FREQ,
+        }
     });
 
+    let ptd = unsafe { &*s32k144::PTD::ptr() };
+    let mut blinker = blink::Blinker::new(blink::Pattern::Sos(blink::Color::Red));
     loop {
-        atomic::compiler_fence(Ordering::SeqCst);
+        if blinker.tick() == blink::Color::Off {
+            ptd.psor.write(|w| unsafe { w.ptso().bits(1 << 15) });
+        } else {
+            ptd.pcor.write(|w| unsafe { w.ptco().bits(1 << 15) });
+        }
+
+        // At least 100 ms, `delay` counts core clock cycles
+        cortex_m::asm::delay(core_freq / 10);
     }
 }
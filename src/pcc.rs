@@ -1,22 +1,50 @@
 //! The peripheral clock controller (PCC) SW module
+//!
+//! Every peripheral with a clock gate in the PCC has a guard type. The clock is gated on by
+//! `Pcc::enable` and gated off again when the guard is dropped.
+//!
+//! Guards can be shared between drivers by cloning them or by calling `Pcc::share`.
+//! The clock is then kept on until the last guard is dropped.
+//!
+//! Peripherals with a functional clock select it from the asynchronous SCG outputs. FTM and
+//! FlexIO use the `*DIV1` outputs (`Div1ClockSource`), all others the `*DIV2` outputs
+//! (`ClockSource`).
+
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use s32k144;
 
+use crate::spc;
+
 #[derive(Debug, PartialEq)]
 pub enum Error {
     RegisterNotPresent,
     AlreadyEnabled,
+    InvalidDivider,
 }
 
-#[derive(Debug, PartialEq)]
-/// Clock source
+/// A functional clock source selection, as encoded in the PCS field
+pub trait FunctionalClockSource: Copy {
+    /// Return the value of the PCS field selecting this source
+    fn bits(self) -> u8;
+
+    /// Return the source selected by the PCS field
+    fn decode(bits: u8) -> Self;
+
+    /// Return the frequency of the clock source if running
+    fn freq(&self, spc: &spc::Spc) -> Option<u32>;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// Clock source from the SCG `*DIV2` outputs
 ///
 /// Is used on the following peripherals
 /// - LPSPI
 /// - LPIT
-/// - FlexIO
 /// - LPI2C
 /// - LPUART
+/// - ADC
+/// - LPTMR
 pub enum ClockSource {
     None,
     Soscdiv2,
@@ -37,24 +65,370 @@ impl From<ClockSource> for u8 {
     }
 }
 
-pub struct PortC<'a> {
-    pcc: &'a s32k144::pcc::RegisterBlock,
+impl FunctionalClockSource for ClockSource {
+    fn bits(self) -> u8 {
+        self.into()
+    }
+
+    fn decode(bits: u8) -> Self {
+        match bits {
+            0b001 => ClockSource::Soscdiv2,
+            0b010 => ClockSource::Sircdiv2,
+            0b011 => ClockSource::Fircdiv2,
+            0b110 => ClockSource::Splldiv2,
+            _ => ClockSource::None,
+        }
+    }
+
+    fn freq(&self, spc: &spc::Spc) -> Option<u32> {
+        match *self {
+            ClockSource::None => None,
+            ClockSource::Soscdiv2 => spc.soscdiv2_freq(),
+            ClockSource::Sircdiv2 => spc.sircdiv2_freq(),
+            ClockSource::Fircdiv2 => spc.fircdiv2_freq(),
+            ClockSource::Splldiv2 => spc.splldiv2_freq(),
+        }
+    }
 }
 
-pub struct PortD<'a> {
-    pcc: &'a s32k144::pcc::RegisterBlock,
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// Clock source from the SCG `*DIV1` outputs
+///
+/// Is used on the following peripherals
+/// - FTM
+/// - FlexIO
+pub enum Div1ClockSource {
+    None,
+    Soscdiv1,
+    Sircdiv1,
+    Fircdiv1,
+    Splldiv1,
 }
 
-pub struct PortE<'a> {
-    pcc: &'a s32k144::pcc::RegisterBlock,
+impl From<Div1ClockSource> for u8 {
+    fn from(cs: Div1ClockSource) -> u8 {
+        match cs {
+            Div1ClockSource::None => 0b000,
+            Div1ClockSource::Soscdiv1 => 0b001,
+            Div1ClockSource::Sircdiv1 => 0b010,
+            Div1ClockSource::Fircdiv1 => 0b011,
+            Div1ClockSource::Splldiv1 => 0b110,
+        }
+    }
 }
 
-pub struct Lpuart1<'a> {
-    pcc: &'a s32k144::pcc::RegisterBlock,
+impl FunctionalClockSource for Div1ClockSource {
+    fn bits(self) -> u8 {
+        self.into()
+    }
+
+    fn decode(bits: u8) -> Self {
+        match bits {
+            0b001 => Div1ClockSource::Soscdiv1,
+            0b010 => Div1ClockSource::Sircdiv1,
+            0b011 => Div1ClockSource::Fircdiv1,
+            0b110 => Div1ClockSource::Splldiv1,
+            _ => Div1ClockSource::None,
+        }
+    }
+
+    fn freq(&self, spc: &spc::Spc) -> Option<u32> {
+        match *self {
+            Div1ClockSource::None => None,
+            Div1ClockSource::Soscdiv1 => spc.soscdiv1_freq(),
+            Div1ClockSource::Sircdiv1 => spc.sircdiv1_freq(),
+            Div1ClockSource::Fircdiv1 => spc.fircdiv1_freq(),
+            Div1ClockSource::Splldiv1 => spc.splldiv1_freq(),
+        }
+    }
 }
 
-pub struct Can0<'a> {
-    pcc: &'a s32k144::pcc::RegisterBlock,
+/// Fractional divider of the functional clock
+///
+/// The functional clock is the source clock multiplied by `(frac + 1) / (pcd + 1)`.
+/// `frac` must not be greater than `pcd`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ClockDivider {
+    /// Divider value (PCD), between 0 and 7
+    pub pcd: u8,
+
+    /// Fraction value (FRAC), 0 or 1
+    pub frac: u8,
+}
+
+impl Default for ClockDivider {
+    /// Divide by 1
+    fn default() -> Self {
+        ClockDivider { pcd: 0, frac: 0 }
+    }
+}
+
+impl ClockDivider {
+    fn is_valid(&self) -> bool {
+        self.pcd <= 7 && self.frac <= 1 && self.frac <= self.pcd
+    }
+
+    fn apply(&self, freq: u32) -> u32 {
+        freq / (u32::from(self.pcd) + 1) * (u32::from(self.frac) + 1)
+    }
+}
+
+mod sealed {
+    use core::sync::atomic::AtomicUsize;
+
+    use s32k144;
+
+    /// Creation of the guards, only done by `Pcc`
+    pub trait ClockGate<'a>: Sized {
+        /// Gate the clock of the peripheral on
+        fn enable_clock(pcc: &s32k144::pcc::RegisterBlock);
+
+        /// Create the guard for a peripheral with its clock gated on
+        fn guard(pcc: &'a s32k144::pcc::RegisterBlock) -> Self;
+
+        /// The number of guards alive for the peripheral
+        fn users() -> &'static AtomicUsize;
+    }
+
+    /// Selection of the functional clock source, only done by `Pcc` while the clock is gated off
+    pub trait SourceSelect {
+        /// Write the PCS field
+        fn set_source_bits(pcc: &s32k144::pcc::RegisterBlock, bits: u8);
+    }
+
+    /// Selection of the fractional divider, only done by `Pcc` while the clock is gated off
+    pub trait DividerSelect {
+        fn set_divider(pcc: &s32k144::pcc::RegisterBlock, divider: super::ClockDivider);
+    }
+}
+
+/// A peripheral with a clock gate in the PCC
+///
+/// This is implemented by the guard types returned from `Pcc::enable`, which are the only way to
+/// create them.
+pub trait PeripheralClock<'a>: sealed::ClockGate<'a> {
+    /// Return true if the peripheral is present on this chip
+    fn is_present(pcc: &s32k144::pcc::RegisterBlock) -> bool;
+
+    /// Return true if the clock of the peripheral is gated on
+    fn is_enabled(pcc: &s32k144::pcc::RegisterBlock) -> bool;
+}
+
+/// Remove a guard from `users`, returning true if it was the last one
+///
+/// The count never wraps below zero.
+fn release(users: &AtomicUsize) -> bool {
+    let mut count = users.load(Ordering::SeqCst);
+    while count > 0 {
+        match users.compare_exchange(count, count - 1, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => return count == 1,
+            Err(current) => count = current,
+        }
+    }
+    false
+}
+
+macro_rules! peripheral_clock {
+    ($($(#[$meta:meta])* $Guard:ident: $register:ident,)+) => {
+        $(
+            $(#[$meta])*
+            pub struct $Guard<'a> {
+                pcc: &'a s32k144::pcc::RegisterBlock,
+            }
+
+            impl<'a> PeripheralClock<'a> for $Guard<'a> {
+                fn is_present(pcc: &s32k144::pcc::RegisterBlock) -> bool {
+                    pcc.$register.read().pr().is_1()
+                }
+
+                fn is_enabled(pcc: &s32k144::pcc::RegisterBlock) -> bool {
+                    pcc.$register.read().cgc().is_1()
+                }
+            }
+
+            impl<'a> sealed::ClockGate<'a> for $Guard<'a> {
+                fn enable_clock(pcc: &s32k144::pcc::RegisterBlock) {
+                    pcc.$register.modify(|_, w| w.cgc()._1());
+                }
+
+                fn guard(pcc: &'a s32k144::pcc::RegisterBlock) -> Self {
+                    $Guard { pcc: pcc }
+                }
+
+                fn users() -> &'static AtomicUsize {
+                    static USERS: AtomicUsize = AtomicUsize::new(0);
+                    &USERS
+                }
+            }
+
+            impl<'a> Clone for $Guard<'a> {
+                /// Share the clock gate, it's kept on until the last guard is dropped
+                fn clone(&self) -> Self {
+                    <Self as sealed::ClockGate<'a>>::users().fetch_add(1, Ordering::SeqCst);
+                    $Guard { pcc: self.pcc }
+                }
+            }
+
+            impl<'a> Drop for $Guard<'a> {
+                fn drop(&mut self) {
+                    if release(<Self as sealed::ClockGate<'a>>::users()) {
+                        self.pcc.$register.reset();
+                    }
+                }
+            }
+        )+
+    };
+}
+
+/// A peripheral with a selectable functional clock in the PCC
+///
+/// The source can only be selected through `Pcc::enable_with_source`, as it must not change while
+/// the clock is gated on.
+pub trait FunctionalClock<'a>: PeripheralClock<'a> + sealed::SourceSelect {
+    /// The clock sources of the peripheral, `ClockSource` or `Div1ClockSource`
+    type Source: FunctionalClockSource;
+
+    /// Return the selected functional clock source
+    fn source(pcc: &s32k144::pcc::RegisterBlock) -> Self::Source;
+
+    /// Return the fractional divider applied to the functional clock
+    fn divider(pcc: &s32k144::pcc::RegisterBlock) -> ClockDivider;
+}
+
+/// A peripheral with a fractional divider on its functional clock in the PCC
+///
+/// On this chip only LPTMR0 has a divider in the PCC. The divider can only be set through
+/// `Pcc::enable_with_divider`.
+pub trait DividedClock<'a>: FunctionalClock<'a> + sealed::DividerSelect {}
+
+macro_rules! functional_clock {
+    ($($(#[$meta:meta])* $Guard:ident: $register:ident => $Source:ident,)+) => {
+        peripheral_clock! {
+            $($(#[$meta])* $Guard: $register,)+
+        }
+
+        $(
+            impl<'a> FunctionalClock<'a> for $Guard<'a> {
+                type Source = $Source;
+
+                fn source(pcc: &s32k144::pcc::RegisterBlock) -> $Source {
+                    $Source::decode(pcc.$register.read().pcs().bits())
+                }
+
+                functional_clock!(@divider $register);
+            }
+
+            impl<'a> sealed::SourceSelect for $Guard<'a> {
+                fn set_source_bits(pcc: &s32k144::pcc::RegisterBlock, bits: u8) {
+                    pcc.$register.modify(|_, w| w.pcs().bits(bits));
+                }
+            }
+
+            impl<'a> $Guard<'a> {
+                /// Return the selected functional clock source
+                pub fn source(&self) -> $Source {
+                    <Self as FunctionalClock<'a>>::source(self.pcc)
+                }
+
+                /// Return the frequency of the functional clock if running
+                pub fn functional_clock_freq(&self, spc: &spc::Spc) -> Option<u32> {
+                    let freq = self.source().freq(spc)?;
+                    Some(<Self as FunctionalClock<'a>>::divider(self.pcc).apply(freq))
+                }
+            }
+        )+
+    };
+    (@divider pcc_lptmr0) => {
+        fn divider(pcc: &s32k144::pcc::RegisterBlock) -> ClockDivider {
+            let reg_value = pcc.pcc_lptmr0.read();
+            ClockDivider {
+                pcd: reg_value.pcd().bits(),
+                frac: reg_value.frac().bit() as u8,
+            }
+        }
+    };
+    (@divider $register:ident) => {
+        fn divider(_pcc: &s32k144::pcc::RegisterBlock) -> ClockDivider {
+            ClockDivider::default()
+        }
+    };
+}
+
+peripheral_clock! {
+    /// Clock guard for PORTA
+    PortA: pcc_porta,
+    /// Clock guard for PORTB
+    PortB: pcc_portb,
+    /// Clock guard for PORTC
+    PortC: pcc_portc,
+    /// Clock guard for PORTD
+    PortD: pcc_portd,
+    /// Clock guard for PORTE
+    PortE: pcc_porte,
+    /// Clock guard for FlexCAN0
+    Can0: pcc_flex_can0,
+    /// Clock guard for FlexCAN1
+    Can1: pcc_flex_can1,
+    /// Clock guard for FlexCAN2
+    Can2: pcc_flex_can2,
+    /// Clock guard for DMAMUX
+    Dmamux: pcc_dmamux,
+    /// Clock guard for CRC
+    Crc: pcc_crc,
+    /// Clock guard for RTC
+    Rtc: pcc_rtc,
+    /// Clock guard for PDB0
+    Pdb0: pcc_pdb0,
+    /// Clock guard for PDB1
+    Pdb1: pcc_pdb1,
+    /// Clock guard for CMP0
+    Cmp0: pcc_cmp0,
+    /// Clock guard for EWM
+    Ewm: pcc_ewm,
+}
+
+functional_clock! {
+    /// Clock guard for LPUART0
+    Lpuart0: pcc_lpuart0 => ClockSource,
+    /// Clock guard for LPUART1
+    Lpuart1: pcc_lpuart1 => ClockSource,
+    /// Clock guard for LPUART2
+    Lpuart2: pcc_lpuart2 => ClockSource,
+    /// Clock guard for LPSPI0
+    Lpspi0: pcc_lpspi0 => ClockSource,
+    /// Clock guard for LPSPI1
+    Lpspi1: pcc_lpspi1 => ClockSource,
+    /// Clock guard for LPSPI2
+    Lpspi2: pcc_lpspi2 => ClockSource,
+    /// Clock guard for LPI2C0
+    Lpi2c0: pcc_lpi2c0 => ClockSource,
+    /// Clock guard for LPIT0
+    Lpit0: pcc_lpit => ClockSource,
+    /// Clock guard for FTM0
+    Ftm0: pcc_ftm0 => Div1ClockSource,
+    /// Clock guard for FTM1
+    Ftm1: pcc_ftm1 => Div1ClockSource,
+    /// Clock guard for FTM2
+    Ftm2: pcc_ftm2 => Div1ClockSource,
+    /// Clock guard for FTM3
+    Ftm3: pcc_ftm3 => Div1ClockSource,
+    /// Clock guard for ADC0
+    Adc0: pcc_adc0 => ClockSource,
+    /// Clock guard for ADC1
+    Adc1: pcc_adc1 => ClockSource,
+    /// Clock guard for FlexIO
+    FlexIo: pcc_flexio => Div1ClockSource,
+    /// Clock guard for LPTMR0
+    Lptmr0: pcc_lptmr0 => ClockSource,
+}
+
+impl<'a> DividedClock<'a> for Lptmr0<'a> {}
+
+impl<'a> sealed::DividerSelect for Lptmr0<'a> {
+    fn set_divider(pcc: &s32k144::pcc::RegisterBlock, divider: ClockDivider) {
+        pcc.pcc_lptmr0
+            .modify(|_, w| unsafe { w.pcd().bits(divider.pcd).frac().bit(divider.frac == 1) });
+    }
 }
 
 pub struct Pcc<'a> {
@@ -66,96 +440,112 @@ impl<'a> Pcc<'a> {
         Pcc { pcc: pcc }
     }
 
-    pub fn enable_portc(&self) -> Result<PortC, Error> {
-        let reg_value = self.pcc.pcc_portc.read();
-        if reg_value.pr().is_0() {
+    /// Gate the clock of a peripheral on
+    ///
+    /// The clock is gated off again when the returned guard, and all its clones, are dropped.
+    ///
+    /// ```rust
+    /// let pcc = Pcc::init(&p.PCC);
+    /// let pcc_lpspi0: pcc::Lpspi0 = pcc.enable().unwrap();
+    /// ```
+    pub fn enable<P: PeripheralClock<'a>>(&self) -> Result<P, Error> {
+        if !P::is_present(self.pcc) {
             Err(Error::RegisterNotPresent)
-        } else if reg_value.cgc().is_1() {
+        } else if P::is_enabled(self.pcc) {
             Err(Error::AlreadyEnabled)
         } else {
-            self.pcc.pcc_portc.modify(|_, w| w.cgc()._1());
-            Ok(PortC { pcc: self.pcc })
+            P::enable_clock(self.pcc);
+            P::users().store(1, Ordering::SeqCst);
+            Ok(P::guard(self.pcc))
         }
     }
 
-    pub fn enable_portd(&self) -> Result<PortD, Error> {
-        let reg_value = self.pcc.pcc_portd.read();
-        if reg_value.pr().is_0() {
-            Err(Error::RegisterNotPresent)
-        } else if reg_value.cgc().is_1() {
-            Err(Error::AlreadyEnabled)
-        } else {
-            self.pcc.pcc_portd.modify(|_, w| w.cgc()._1());
-            Ok(PortD { pcc: self.pcc })
-        }
-    }
-
-    pub fn enable_porte(&self) -> Result<PortE, Error> {
-        let reg_value = self.pcc.pcc_porte.read();
-        if reg_value.pr().is_0() {
-            Err(Error::RegisterNotPresent)
-        } else if reg_value.cgc().is_1() {
-            Err(Error::AlreadyEnabled)
-        } else {
-            self.pcc.pcc_porte.modify(|_, w| w.cgc()._1());
-            Ok(PortE { pcc: self.pcc })
+    /// Share the clock gate of a peripheral with the existing guards, or gate it on if there are none
+    ///
+    /// `Error::AlreadyEnabled` is only returned if the clock was gated on without a guard.
+    /// A shared functional clock keeps the source it was enabled with.
+    ///
+    /// ```rust
+    /// let pcc = Pcc::init(&p.PCC);
+    /// let pcc_portd_led: pcc::PortD = pcc.share().unwrap();
+    /// let pcc_portd_gpio: pcc::PortD = pcc.share().unwrap();
+    /// ```
+    pub fn share<P: PeripheralClock<'a>>(&self) -> Result<P, Error> {
+        let users = P::users();
+        let mut count = users.load(Ordering::SeqCst);
+        while count > 0 {
+            match users.compare_exchange(count, count + 1, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return Ok(P::guard(self.pcc)),
+                Err(current) => count = current,
+            }
         }
+        self.enable()
     }
 
-    pub fn enable_lpuart1(&self, source: ClockSource) -> Result<Lpuart1, Error> {
-        let reg_value = self.pcc.pcc_lpuart1.read();
-        if reg_value.pr().is_0() {
+    /// Select the functional clock source and gate the clock of a peripheral on
+    ///
+    /// FTM and FlexIO are clocked from the SCG `*DIV1` outputs and take a `Div1ClockSource`, the
+    /// other peripherals from the `*DIV2` outputs and take a `ClockSource`.
+    ///
+    /// ```rust
+    /// let pcc = Pcc::init(&p.PCC);
+    /// let pcc_lpit0: pcc::Lpit0 = pcc.enable_with_source(pcc::ClockSource::Sircdiv2).unwrap();
+    /// let lpit_freq = pcc_lpit0.functional_clock_freq(&spc);
+    /// ```
+    pub fn enable_with_source<P: FunctionalClock<'a>>(
+        &self,
+        source: P::Source,
+    ) -> Result<P, Error> {
+        if !P::is_present(self.pcc) {
             Err(Error::RegisterNotPresent)
-        } else if reg_value.cgc().is_1() {
+        } else if P::is_enabled(self.pcc) {
             Err(Error::AlreadyEnabled)
         } else {
-            self.pcc
-                .pcc_lpuart1
-                .modify(|_, w| w.pcs().bits(u8::from(source)));
-            self.pcc.pcc_lpuart1.modify(|_, w| w.cgc()._1());
-            Ok(Lpuart1 { pcc: self.pcc })
+            P::set_source_bits(self.pcc, source.bits());
+            P::enable_clock(self.pcc);
+            P::users().store(1, Ordering::SeqCst);
+            Ok(P::guard(self.pcc))
         }
     }
 
-    pub fn enable_can0(&self) -> Result<Can0, Error> {
-        let reg_value = self.pcc.pcc_flex_can0.read();
-        if reg_value.pr().is_0() {
+    /// Select the functional clock source and divider, and gate the clock of a peripheral on
+    pub fn enable_with_divider<P: DividedClock<'a>>(
+        &self,
+        source: P::Source,
+        divider: ClockDivider,
+    ) -> Result<P, Error> {
+        if !divider.is_valid() {
+            Err(Error::InvalidDivider)
+        } else if !P::is_present(self.pcc) {
             Err(Error::RegisterNotPresent)
-        } else if reg_value.cgc().is_1() {
+        } else if P::is_enabled(self.pcc) {
             Err(Error::AlreadyEnabled)
         } else {
-            self.pcc.pcc_flex_can0.modify(|_, w| w.cgc()._1());
-            Ok(Can0 { pcc: self.pcc })
+            P::set_divider(self.pcc, divider);
+            P::set_source_bits(self.pcc, source.bits());
+            P::enable_clock(self.pcc);
+            P::users().store(1, Ordering::SeqCst);
+            Ok(P::guard(self.pcc))
         }
     }
-}
 
-impl<'a> Drop for PortC<'a> {
-    fn drop(&mut self) {
-        self.pcc.pcc_portc.reset();
+    pub fn enable_portc(&self) -> Result<PortC<'a>, Error> {
+        self.enable()
     }
-}
 
-impl<'a> Drop for PortD<'a> {
-    fn drop(&mut self) {
-        self.pcc.pcc_portd.reset();
+    pub fn enable_portd(&self) -> Result<PortD<'a>, Error> {
+        self.enable()
     }
-}
 
-impl<'a> Drop for PortE<'a> {
-    fn drop(&mut self) {
-        self.pcc.pcc_porte.reset();
+    pub fn enable_porte(&self) -> Result<PortE<'a>, Error> {
+        self.enable()
     }
-}
 
-impl<'a> Drop for Lpuart1<'a> {
-    fn drop(&mut self) {
-        self.pcc.pcc_lpuart1.reset();
+    pub fn enable_lpuart1(&self, source: ClockSource) -> Result<Lpuart1<'a>, Error> {
+        self.enable_with_source(source)
     }
-}
 
-impl<'a> Drop for Can0<'a> {
-    fn drop(&mut self) {
-        self.pcc.pcc_flex_can0.reset();
+    pub fn enable_can0(&self) -> Result<Can0<'a>, Error> {
+        self.enable()
     }
 }
@@ -0,0 +1,125 @@
+//! The Power Management Controller (PMC) SW module
+//!
+//! The low voltage detect (LVD) and low voltage warning (LVW) trip points are fixed in hardware
+//! on this chip and can't be configured. See the datasheet for their values.
+
+use s32k144;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    /// The low voltage reset was disabled by an earlier write, it's enabled again by the next reset
+    ResetLocked,
+}
+
+/// Action taken when a low voltage is detected
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LowVoltageAction {
+    /// Only set the flag, it can be polled with `Pmc::low_voltage_detected`
+    None,
+
+    /// Generate an interrupt request (`LVD_LVW`)
+    Interrupt,
+
+    /// Reset the MCU
+    Reset,
+}
+
+/// The Power Management Controller instance
+pub struct Pmc<'a> {
+    pmc: &'a s32k144::pmc::RegisterBlock,
+}
+
+impl<'a> Pmc<'a> {
+    pub fn init(pmc: &'a s32k144::pmc::RegisterBlock) -> Self {
+        Pmc { pmc: pmc }
+    }
+
+    /// Select what happens when the supply drops below the low voltage detect level
+    ///
+    /// The reset enable (LVDRE) is set after power-on reset, and the first write to the register
+    /// locks it until the next reset. Selecting `LowVoltageAction::None` or
+    /// `LowVoltageAction::Interrupt` therefore disables the low voltage reset until the next reset,
+    /// while `LowVoltageAction::Reset` and `clear_low_voltage_detected` lock it enabled. Selecting
+    /// `LowVoltageAction::Reset` once it's disabled returns `Error::ResetLocked`.
+    pub fn set_low_voltage_detect(&self, action: LowVoltageAction) -> Result<(), Error> {
+        let reset = action == LowVoltageAction::Reset;
+        if reset && self.pmc.lvdsc1.read().lvdre().bit_is_clear() {
+            return Err(Error::ResetLocked);
+        }
+
+        self.pmc.lvdsc1.modify(|_, w| {
+            w.lvdie()
+                .bit(action == LowVoltageAction::Interrupt)
+                .lvdre()
+                .bit(reset)
+        });
+        Ok(())
+    }
+
+    /// Enable or disable the interrupt request when the supply drops below the warning level
+    pub fn set_low_voltage_warning_interrupt(&self, enable: bool) {
+        self.pmc.lvdsc2.modify(|_, w| w.lvwie().bit(enable));
+    }
+
+    /// Return true if a low voltage detect event has occured since the flag was cleared
+    pub fn low_voltage_detected(&self) -> bool {
+        self.pmc.lvdsc1.read().lvdf().bit_is_set()
+    }
+
+    /// Clear the low voltage detect flag
+    pub fn clear_low_voltage_detected(&self) {
+        self.pmc.lvdsc1.modify(|_, w| w.lvdack().set_bit());
+    }
+
+    /// Return true if a low voltage warning event has occured since the flag was cleared
+    pub fn low_voltage_warning(&self) -> bool {
+        self.pmc.lvdsc2.read().lvwf().bit_is_set()
+    }
+
+    /// Clear the low voltage warning flag
+    pub fn clear_low_voltage_warning(&self) {
+        self.pmc.lvdsc2.modify(|_, w| w.lvwack().set_bit());
+    }
+
+    /// Enable or disable the bias generator
+    ///
+    /// The bias must be enabled before entering VLPR or VLPS.
+    pub fn set_bias(&self, enable: bool) {
+        self.pmc.regsc.modify(|_, w| w.biasen().bit(enable));
+    }
+
+    /// Enable or disable the 128 kHz low power oscillator (LPO)
+    pub fn set_lpo(&self, enable: bool) {
+        self.pmc.regsc.modify(|_, w| w.lpodis().bit(!enable));
+    }
+
+    /// Return true if the low power oscillator is running
+    pub fn lpo_enabled(&self) -> bool {
+        self.pmc.regsc.read().lpostat().bit_is_set()
+    }
+
+    /// Return the trim value of the low power oscillator
+    ///
+    /// The value is between -16 and 15, where each step is about 1% of the LPO frequency.
+    pub fn lpo_trim(&self) -> i8 {
+        let bits = self.pmc.lpotrim.read().lpotrim().bits() as i8;
+        // Sign extend the 5 bit two's complement value
+        (bits << 3) >> 3
+    }
+
+    /// Set the trim value of the low power oscillator
+    ///
+    /// The value is clamped to between -16 and 15.
+    pub fn set_lpo_trim(&self, trim: i8) {
+        let trim = if trim < -16 {
+            -16
+        } else if trim > 15 {
+            15
+        } else {
+            trim
+        };
+        self.pmc
+            .lpotrim
+            .write(|w| unsafe { w.lpotrim().bits((trim as u8) & 0b1_1111) });
+    }
+}
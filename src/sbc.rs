@@ -0,0 +1,556 @@
+//! The UJA1169 system basis chip (SBC)
+//!
+//! The SBC supplies the CAN transceiver and has a watchdog that resets the MCU. It's connected to
+//! LPSPI1, with SCK on PTB14, SIN on PTB15, SOUT on PTB16 and the chip select on PCS3 (PTB17).
+//!
+//! A fresh UJA1169 starts in forced normal mode, with the watchdog off and the CAN transceiver
+//! active. `Sbc::init` puts it in a known state either way, so CAN works the same after every
+//! reset. Outside forced normal mode this arms the watchdog, which then resets the MCU unless
+//! `Sbc::kick` is called within the watchdog period, see `Sbc::is_watchdog_running`.
+//!
+//! ```rust
+//! let pcc_lpspi1: pcc::Lpspi1 = pcc.enable_with_source(pcc::ClockSource::Sircdiv2).unwrap();
+//! let pcc_portb: pcc::PortB = pcc.enable().unwrap();
+//! let mut sbc = sbc::Sbc::init(
+//!     &p.LPSPI1,
+//!     &p.PORTB,
+//!     &pcc_lpspi1,
+//!     &pcc_portb,
+//!     &spc,
+//!     sbc::Config::default(),
+//! )
+//! .unwrap();
+//! sbc.set_can_mode(sbc::CanMode::ListenOnly).unwrap();
+//!
+//! loop {
+//!     // At least once every 1024 ms with the default config
+//!     sbc.kick().unwrap();
+//! }
+//! ```
+
+#![allow(dead_code)]
+
+use embedded_hal::blocking::spi::Transfer;
+use embedded_hal::spi::MODE_1;
+use s32k144;
+
+use crate::{lpspi, pcc, spc};
+
+/// Register addresses
+const WATCHDOG_CONTROL: u8 = 0x00;
+const MODE_CONTROL: u8 = 0x01;
+const REGULATOR_CONTROL: u8 = 0x10;
+const CAN_CONTROL: u8 = 0x20;
+const GLOBAL_EVENT_STATUS: u8 = 0x60;
+const SYSTEM_EVENT_STATUS: u8 = 0x61;
+const TRANSCEIVER_EVENT_STATUS: u8 = 0x63;
+const WAKE_PIN_EVENT_STATUS: u8 = 0x64;
+const MTPNV_STATUS: u8 = 0x70;
+const STARTUP_CONTROL: u8 = 0x73;
+const SBC_CONFIG_CONTROL: u8 = 0x74;
+const MTPNV_CRC_CONTROL: u8 = 0x75;
+const IDENTIFICATION: u8 = 0x7E;
+
+/// Read-only bit of a frame, set to read the register
+const FRAME_READ: u16 = 1 << 8;
+
+/// MTPNV status bits, the write counter is in the upper six bits
+const NVMPS: u8 = 1 << 0;
+const WRCNTS_SHIFT: u8 = 2;
+
+/// Number of times the MTPNV status is read while waiting for programming to finish, each read
+/// takes a few microseconds
+const MTPNV_STATUS_CHECKS: u32 = 10_000;
+
+/// Forced normal mode control bit in the SBC configuration control register
+const FNMC: u8 = 1 << 3;
+
+/// V2/VEXT control bits in the regulator control register
+const V2C_SHIFT: u8 = 2;
+const V2C_MASK: u8 = 0b11 << V2C_SHIFT;
+
+/// CAN mode control bits in the CAN control register
+const CMC_MASK: u8 = 0b11;
+
+/// Global event status bits
+const GES_SYSTEM: u8 = 1 << 0;
+const GES_TRANSCEIVER: u8 = 1 << 2;
+const GES_WAKE_PIN: u8 = 1 << 3;
+
+/// System event status bits
+const SES_WATCHDOG_FAIL: u8 = 1 << 0;
+const SES_SPI_FAIL: u8 = 1 << 1;
+const SES_OVERTEMPERATURE: u8 = 1 << 2;
+const SES_POWER_ON: u8 = 1 << 4;
+
+/// Transceiver event status bits
+const TES_CAN_WAKE_UP: u8 = 1 << 0;
+const TES_CAN_FAILURE: u8 = 1 << 1;
+const TES_CAN_BUS_SILENCE: u8 = 1 << 4;
+
+/// WAKE pin event status bits
+const WPES_FALLING: u8 = 1 << 0;
+const WPES_RISING: u8 = 1 << 1;
+
+/// The SBC accepts SPI clocks up to 4 MHz
+const BAUDRATE: u32 = 1_000_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Error {
+    Spi(lpspi::Error),
+
+    /// A register didn't hold the written value when read back
+    WriteFailed,
+
+    /// The non-volatile memory can't be programmed, see `Sbc::set_forced_normal`
+    MtpnvLocked,
+}
+
+impl From<lpspi::Error> for Error {
+    fn from(error: lpspi::Error) -> Self {
+        Error::Spi(error)
+    }
+}
+
+/// Operating mode of the SBC
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    /// V1, and thereby the MCU, is off until a wake-up event
+    Sleep,
+
+    /// The CAN transceiver is off, but can wake up the SBC
+    Standby,
+
+    Normal,
+}
+
+impl Mode {
+    fn bits(self) -> u8 {
+        match self {
+            Mode::Sleep => 0b001,
+            Mode::Standby => 0b100,
+            Mode::Normal => 0b111,
+        }
+    }
+}
+
+/// Watchdog operating mode, the watchdog is off in forced normal mode regardless of this
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WatchdogMode {
+    /// Off in Sleep mode, and in Standby mode while the MCU doesn't use the SPI; timeout mode
+    /// otherwise
+    Autonomous,
+
+    /// Must be triggered before the period ends
+    Timeout,
+
+    /// Must be triggered in the second half of the period
+    Window,
+}
+
+impl WatchdogMode {
+    fn bits(self) -> u8 {
+        match self {
+            WatchdogMode::Autonomous => 0b001,
+            WatchdogMode::Timeout => 0b010,
+            WatchdogMode::Window => 0b100,
+        }
+    }
+}
+
+/// Nominal watchdog period
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WatchdogPeriod {
+    Ms8,
+    Ms16,
+    Ms32,
+    Ms64,
+    Ms128,
+    Ms256,
+    Ms1024,
+    Ms4096,
+}
+
+impl WatchdogPeriod {
+    fn bits(self) -> u8 {
+        match self {
+            WatchdogPeriod::Ms8 => 0b1000,
+            WatchdogPeriod::Ms16 => 0b0001,
+            WatchdogPeriod::Ms32 => 0b0010,
+            WatchdogPeriod::Ms64 => 0b1011,
+            WatchdogPeriod::Ms128 => 0b0100,
+            WatchdogPeriod::Ms256 => 0b1101,
+            WatchdogPeriod::Ms1024 => 0b1110,
+            WatchdogPeriod::Ms4096 => 0b0111,
+        }
+    }
+}
+
+/// Operating mode of the CAN transceiver, while the SBC is in normal mode
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CanMode {
+    /// The transceiver is off
+    Offline,
+
+    /// Transmitting and receiving, and off if V1 is undervoltage
+    Active,
+
+    /// Transmitting and receiving, regardless of V1 undervoltage
+    ActiveWithoutUndervoltage,
+
+    /// Only receiving
+    ListenOnly,
+}
+
+impl CanMode {
+    fn bits(self) -> u8 {
+        match self {
+            CanMode::Offline => 0b00,
+            CanMode::Active => 0b01,
+            CanMode::ActiveWithoutUndervoltage => 0b10,
+            CanMode::ListenOnly => 0b11,
+        }
+    }
+}
+
+/// When the V2/VEXT supply is on
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum V2Mode {
+    Off,
+    Normal,
+    NormalStandby,
+    NormalStandbySleep,
+}
+
+impl V2Mode {
+    fn bits(self) -> u8 {
+        match self {
+            V2Mode::Off => 0b00,
+            V2Mode::Normal => 0b01,
+            V2Mode::NormalStandby => 0b10,
+            V2Mode::NormalStandbySleep => 0b11,
+        }
+    }
+}
+
+/// Events that occured since they were last cleared, e.g. what woke up the SBC
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct WakeSources {
+    pub power_on: bool,
+    pub watchdog_failure: bool,
+    pub spi_failure: bool,
+    pub overtemperature: bool,
+    pub can_wake_up: bool,
+    pub can_failure: bool,
+    pub can_bus_silence: bool,
+    pub wake_pin_rising: bool,
+    pub wake_pin_falling: bool,
+}
+
+/// Configuration applied by `Sbc::init`
+///
+/// The watchdog settings only apply outside forced normal mode. The default arms the watchdog
+/// with a 1024 ms period, so `Sbc::kick` must be called at least that often.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Config {
+    pub watchdog_mode: WatchdogMode,
+    pub watchdog_period: WatchdogPeriod,
+    pub can_mode: CanMode,
+    pub v2: V2Mode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            watchdog_mode: WatchdogMode::Autonomous,
+            watchdog_period: WatchdogPeriod::Ms1024,
+            can_mode: CanMode::Active,
+            v2: V2Mode::Off,
+        }
+    }
+}
+
+/// Return the CRC protecting the non-volatile registers, starting at the startup control register
+///
+/// The CRC uses the polynomial 0x2F and the initial value 0xFF.
+pub fn mtpnv_crc(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0xFF;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x2F
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+pub struct Sbc<'a> {
+    spi: lpspi::Lpspi<'a>,
+    pcc_lpspi1: &'a pcc::Lpspi1<'a>,
+    pcc_portb: &'a pcc::PortB<'a>,
+    config: Config,
+    watchdog_running: bool,
+}
+
+impl<'a> Sbc<'a> {
+    /// Mux the SPI pins to LPSPI1, and put the SBC in normal mode with `config` applied
+    ///
+    /// Pending events are cleared, read them with `wake_sources` before calling this if needed.
+    ///
+    /// Unless the SBC is in forced normal mode, the watchdog is started with the mode and period
+    /// of `config`, and `kick` must be called within the period from then on to avoid a reset.
+    /// `is_watchdog_running` tells which case applies.
+    pub fn init(
+        lpspi1: &'a s32k144::lpspi0::RegisterBlock,
+        portb: &'a s32k144::portb::RegisterBlock,
+        pcc_lpspi1: &'a pcc::Lpspi1,
+        pcc_portb: &'a pcc::PortB,
+        spc: &spc::Spc,
+        config: Config,
+    ) -> Result<Self, Error> {
+        // The SBC samples on the falling edge, and the chip select must be released after each
+        // 16-bit frame
+        let spi_config = lpspi::Config {
+            baudrate: BAUDRATE,
+            mode: MODE_1,
+            frame_size: 16,
+            pcs: lpspi::Pcs::Pcs3,
+            continuous: false,
+            ..Default::default()
+        };
+        let spi = lpspi::Lpspi::init(lpspi1, pcc_lpspi1, spc, spi_config)?;
+
+        portb.pcr14.modify(|_, w| w.mux().bits(0b011));
+        portb.pcr15.modify(|_, w| w.mux().bits(0b011));
+        portb.pcr16.modify(|_, w| w.mux().bits(0b011));
+        portb.pcr17.modify(|_, w| w.mux().bits(0b011));
+
+        let mut sbc = Sbc {
+            spi: spi,
+            pcc_lpspi1: pcc_lpspi1,
+            pcc_portb: pcc_portb,
+            config: config,
+            watchdog_running: false,
+        };
+
+        sbc.clear_wake_sources()?;
+        if !sbc.is_forced_normal()? {
+            sbc.set_watchdog(config.watchdog_mode, config.watchdog_period)?;
+            sbc.set_mode(Mode::Normal)?;
+            sbc.watchdog_running = true;
+        }
+        sbc.set_v2(config.v2)?;
+        sbc.set_can_mode(config.can_mode)?;
+
+        Ok(sbc)
+    }
+
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Return true if `init` started the watchdog, which must then be triggered with `kick`
+    pub fn is_watchdog_running(&self) -> bool {
+        self.watchdog_running
+    }
+
+    /// Read a register
+    pub fn read(&mut self, address: u8) -> Result<u8, Error> {
+        let mut frame = [(u16::from(address & 0x7F) << 9) | FRAME_READ];
+        self.spi.transfer(&mut frame)?;
+        Ok(frame[0] as u8)
+    }
+
+    /// Write a register
+    pub fn write(&mut self, address: u8, value: u8) -> Result<(), Error> {
+        let mut frame = [(u16::from(address & 0x7F) << 9) | u16::from(value)];
+        self.spi.transfer(&mut frame)?;
+        Ok(())
+    }
+
+    /// Write the bits of a register in `mask`, and check that they were written
+    fn modify(&mut self, address: u8, mask: u8, value: u8) -> Result<(), Error> {
+        let current = self.read(address)?;
+        self.write(address, (current & !mask) | (value & mask))?;
+        if self.read(address)? & mask == value & mask {
+            Ok(())
+        } else {
+            Err(Error::WriteFailed)
+        }
+    }
+
+    /// Return the device identification register
+    pub fn id(&mut self) -> Result<u8, Error> {
+        self.read(IDENTIFICATION)
+    }
+
+    pub fn mode(&mut self) -> Result<Option<Mode>, Error> {
+        Ok(match self.read(MODE_CONTROL)? & 0b111 {
+            0b001 => Some(Mode::Sleep),
+            0b100 => Some(Mode::Standby),
+            0b111 => Some(Mode::Normal),
+            _ => None,
+        })
+    }
+
+    /// Change the operating mode
+    ///
+    /// Entering sleep mode turns off V1, which powers the MCU, unless no wake-up source is
+    /// enabled, in which case the SBC stays in its current mode.
+    pub fn set_mode(&mut self, mode: Mode) -> Result<(), Error> {
+        self.write(MODE_CONTROL, mode.bits())
+    }
+
+    /// Configure the watchdog, this also triggers it
+    pub fn set_watchdog(
+        &mut self,
+        mode: WatchdogMode,
+        period: WatchdogPeriod,
+    ) -> Result<(), Error> {
+        // The watchdog mode can only be changed in standby mode
+        let previous = self.mode()?;
+        if self.read(WATCHDOG_CONTROL)? >> 5 != mode.bits() {
+            self.set_mode(Mode::Standby)?;
+        }
+        self.write(WATCHDOG_CONTROL, (mode.bits() << 5) | period.bits())?;
+        if let Some(previous) = previous {
+            self.set_mode(previous)?;
+        }
+
+        self.config.watchdog_mode = mode;
+        self.config.watchdog_period = period;
+        Ok(())
+    }
+
+    /// Trigger the watchdog, restarting its period
+    pub fn kick(&mut self) -> Result<(), Error> {
+        let value = (self.config.watchdog_mode.bits() << 5) | self.config.watchdog_period.bits();
+        self.write(WATCHDOG_CONTROL, value)
+    }
+
+    pub fn set_can_mode(&mut self, mode: CanMode) -> Result<(), Error> {
+        self.modify(CAN_CONTROL, CMC_MASK, mode.bits())?;
+        self.config.can_mode = mode;
+        Ok(())
+    }
+
+    pub fn set_v2(&mut self, mode: V2Mode) -> Result<(), Error> {
+        self.modify(REGULATOR_CONTROL, V2C_MASK, mode.bits() << V2C_SHIFT)?;
+        self.config.v2 = mode;
+        Ok(())
+    }
+
+    /// Return true if the SBC is in forced normal mode, with the watchdog off
+    pub fn is_forced_normal(&mut self) -> Result<bool, Error> {
+        Ok(self.read(SBC_CONFIG_CONTROL)? & FNMC != 0)
+    }
+
+    /// Return the number of times the non-volatile memory has been programmed
+    pub fn mtpnv_write_count(&mut self) -> Result<u8, Error> {
+        Ok(self.read(MTPNV_STATUS)? >> WRCNTS_SHIFT)
+    }
+
+    /// Enable or disable forced normal mode, returning true if the non-volatile memory was written
+    ///
+    /// The setting is kept in non-volatile memory with a limited number of write cycles, so it's
+    /// only written if it changes. `Error::MtpnvLocked` is returned if the MTPNV status reports
+    /// that the memory can't be programmed, and `Error::WriteFailed` if the write counter or the
+    /// setting didn't change afterwards. The SBC may reset itself, and thereby the MCU, after
+    /// programming, in which case this doesn't return.
+    pub fn set_forced_normal(&mut self, enable: bool) -> Result<bool, Error> {
+        let status = self.read(MTPNV_STATUS)?;
+        let startup = self.read(STARTUP_CONTROL)?;
+        let current = self.read(SBC_CONFIG_CONTROL)?;
+        let config = if enable {
+            current | FNMC
+        } else {
+            current & !FNMC
+        };
+        if config == current {
+            return Ok(false);
+        }
+        if status & NVMPS == 0 {
+            return Err(Error::MtpnvLocked);
+        }
+
+        // The non-volatile registers can only be written in standby mode, and are programmed
+        // when a valid CRC is written
+        self.set_mode(Mode::Standby)?;
+        self.write(SBC_CONFIG_CONTROL, config)?;
+        self.write(MTPNV_CRC_CONTROL, mtpnv_crc(&[startup, config]))?;
+
+        // The write counter is incremented when programming has finished
+        let mut checks = 0;
+        while self.mtpnv_write_count()? == status >> WRCNTS_SHIFT {
+            checks += 1;
+            if checks >= MTPNV_STATUS_CHECKS {
+                return Err(Error::WriteFailed);
+            }
+        }
+        if self.read(SBC_CONFIG_CONTROL)? == config {
+            Ok(true)
+        } else {
+            Err(Error::WriteFailed)
+        }
+    }
+
+    /// Read the events that occured since they were last cleared
+    pub fn wake_sources(&mut self) -> Result<WakeSources, Error> {
+        let global = self.read(GLOBAL_EVENT_STATUS)?;
+        let system = if global & GES_SYSTEM != 0 {
+            self.read(SYSTEM_EVENT_STATUS)?
+        } else {
+            0
+        };
+        let transceiver = if global & GES_TRANSCEIVER != 0 {
+            self.read(TRANSCEIVER_EVENT_STATUS)?
+        } else {
+            0
+        };
+        let wake_pin = if global & GES_WAKE_PIN != 0 {
+            self.read(WAKE_PIN_EVENT_STATUS)?
+        } else {
+            0
+        };
+
+        Ok(WakeSources {
+            power_on: system & SES_POWER_ON != 0,
+            watchdog_failure: system & SES_WATCHDOG_FAIL != 0,
+            spi_failure: system & SES_SPI_FAIL != 0,
+            overtemperature: system & SES_OVERTEMPERATURE != 0,
+            can_wake_up: transceiver & TES_CAN_WAKE_UP != 0,
+            can_failure: transceiver & TES_CAN_FAILURE != 0,
+            can_bus_silence: transceiver & TES_CAN_BUS_SILENCE != 0,
+            wake_pin_rising: wake_pin & WPES_RISING != 0,
+            wake_pin_falling: wake_pin & WPES_FALLING != 0,
+        })
+    }
+
+    /// Clear all events, the event status registers are write 1 to clear
+    pub fn clear_wake_sources(&mut self) -> Result<(), Error> {
+        self.write(SYSTEM_EVENT_STATUS, 0xFF)?;
+        self.write(TRANSCEIVER_EVENT_STATUS, 0xFF)?;
+        self.write(WAKE_PIN_EVENT_STATUS, 0xFF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_check_value() {
+        // CRC-8/AUTOSAR has the same polynomial and initial value, and a final XOR of 0xFF,
+        // giving 0xDF for the standard check input
+        assert_eq!(mtpnv_crc(b"123456789"), 0xDF ^ 0xFF);
+    }
+
+    #[test]
+    fn crc_of_no_data_is_the_initial_value() {
+        assert_eq!(mtpnv_crc(&[]), 0xFF);
+    }
+}
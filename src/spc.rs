@@ -8,8 +8,11 @@
 
 #![allow(dead_code)]
 
+use cortex_m::peripheral::{NVIC, SCB};
 use s32k144;
 
+use crate::{pcc, pmc};
+
 /// Configurations for the System Clock Generator
 #[derive(Default, Debug, PartialEq, Clone)]
 pub struct Config {
@@ -19,6 +22,16 @@ pub struct Config {
     /// Clock divider for `CORE_CLK` and `SYS_CLK`.
     pub div_core: DivCore,
 
+    /// Clock divider for `BUS_CLK`.
+    ///
+    /// `BUS_CLK` is derived from the divided `CORE_CLK`.
+    pub div_bus: DivBus,
+
+    /// Clock divider for `FLASH_CLK`.
+    ///
+    /// `FLASH_CLK` is derived from the divided `CORE_CLK`.
+    pub div_slow: DivSlow,
+
     /// Set the configuration of XTAL and EXTAL pins.
     pub system_oscillator: SystemOscillatorInput,
 
@@ -31,6 +44,53 @@ pub struct Config {
     ///
     /// This should be configured to 40MHz or less in RUN/HSRUN mode.
     pub soscdiv2: SystemOscillatorOutput,
+
+    /// Set the configuration of the system PLL.
+    ///
+    /// `None` leaves the PLL disabled.
+    pub system_pll: Option<SystemPll>,
+
+    /// Set the divider for the sircdiv1_clk
+    pub sircdiv1: SystemOscillatorOutput,
+
+    /// Set the divider for the sircdiv2_clk
+    pub sircdiv2: SystemOscillatorOutput,
+
+    /// Set the divider for the fircdiv1_clk
+    pub fircdiv1: SystemOscillatorOutput,
+
+    /// Set the divider for the fircdiv2_clk
+    pub fircdiv2: SystemOscillatorOutput,
+
+    /// Set the clock monitor of the system oscillator.
+    pub sosc_monitor: ClockMonitor,
+
+    /// Set the clock monitor of the system PLL.
+    pub spll_monitor: ClockMonitor,
+}
+
+/// Reaction of a clock monitor when it detects loss of its clock
+///
+/// A monitored clock that stops is reported by `Spc::clock_loss`, and `Spc::recover_from_clock_loss`
+/// falls back to FIRC. The error flags are write 1 to clear, and are only cleared by
+/// `Spc::recover_from_clock_loss`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClockMonitor {
+    /// The clock is not monitored
+    Disabled,
+
+    /// Loss of clock generates an interrupt request on the `SCG` vector (IRQ 57), which must be
+    /// unmasked in the NVIC
+    Interrupt,
+
+    /// Loss of clock resets the MCU, the reset is reported as a loss of clock reset by the RCM
+    Reset,
+}
+
+impl Default for ClockMonitor {
+    fn default() -> Self {
+        ClockMonitor::Disabled
+    }
 }
 
 /// Set the configuration of XTAL and EXTAL pins.
@@ -65,6 +125,73 @@ impl Default for SystemOscillatorInput {
     }
 }
 
+/// Configuration of the System Phase Locked Loop (SPLL)
+///
+/// The SPLL uses the system oscillator as reference. The resulting clock is
+/// `SPLL_CLK = (SOSC_CLK / prediv) * mult / 2`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SystemPll {
+    /// Divider applied to the system oscillator clock (1 to 8).
+    ///
+    /// The resulting reference clock must be between 8 MHz and 16 MHz.
+    pub prediv: u8,
+
+    /// Multiplication factor of the VCO (16 to 47).
+    ///
+    /// The resulting `VCO_CLK` must be between 180 MHz and 320 MHz.
+    pub mult: u8,
+
+    /// Set the divider for the splldiv1_clk
+    pub splldiv1: SystemOscillatorOutput,
+
+    /// Set the divider for the splldiv2_clk
+    pub splldiv2: SystemOscillatorOutput,
+}
+
+impl SystemPll {
+    const PREDIV_MIN: u8 = 1;
+    const PREDIV_MAX: u8 = 8;
+    const MULT_MIN: u8 = 16;
+    const MULT_MAX: u8 = 47;
+
+    const REFERENCE_MIN: u32 = 8_000_000;
+    const REFERENCE_MAX: u32 = 16_000_000;
+    const VCO_MIN: u32 = 180_000_000;
+    const VCO_MAX: u32 = 320_000_000;
+
+    /// Return the frequency of `VCO_CLK` given the system oscillator frequency
+    pub fn vco_freq(&self, sosc_freq: u32) -> u32 {
+        sosc_freq / u32::from(self.prediv) * u32::from(self.mult)
+    }
+
+    /// Return the frequency of `SPLL_CLK` given the system oscillator frequency
+    pub fn freq(&self, sosc_freq: u32) -> u32 {
+        self.vco_freq(sosc_freq) / 2
+    }
+
+    fn validate(&self, sosc_freq: u32) -> Result<(), Error> {
+        if self.prediv < Self::PREDIV_MIN
+            || self.prediv > Self::PREDIV_MAX
+            || self.mult < Self::MULT_MIN
+            || self.mult > Self::MULT_MAX
+        {
+            return Err(Error::SystemPllFactorOutOfRange);
+        }
+
+        let reference = sosc_freq / u32::from(self.prediv);
+        if reference < Self::REFERENCE_MIN || reference > Self::REFERENCE_MAX {
+            return Err(Error::SystemPllReferenceOutOfRange);
+        }
+
+        let vco = self.vco_freq(sosc_freq);
+        if vco < Self::VCO_MIN || vco > Self::VCO_MAX {
+            return Err(Error::SystemPllVcoOutOfRange);
+        }
+
+        Ok(())
+    }
+}
+
 /// SCG Run Modes
 ///
 /// See section 26.4.1 in datasheet for a full description
@@ -87,9 +214,38 @@ pub enum Mode {
     HighSpeed(HighSpeedMode),
 
     /// Very low power mode
+    ///
+    /// - `CORE_CLK` and `SYS_CLK` clock freuqency must be 4 MHz or less.
+    /// - `BUS_CLK` clock frequency must be 4 MHz or less.
+    /// - `FLASH_CLK` clock frequency must be 1 MHz or less.
+    /// - The core clock to flash clock ratio is limited to a max value of 8.
     VeryLowPower(VeryLowPowerMode),
 }
 
+impl Mode {
+    /// Return the power mode this mode belongs to
+    pub fn power_mode(&self) -> PowerMode {
+        match *self {
+            Mode::Run(_) => PowerMode::Run,
+            Mode::HighSpeed(_) => PowerMode::HighSpeed,
+            Mode::VeryLowPower(_) => PowerMode::VeryLowPower,
+        }
+    }
+}
+
+/// The power modes of `Mode` without the clock selection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PowerMode {
+    /// Run mode
+    Run,
+
+    /// High Speed Run mode
+    HighSpeed,
+
+    /// Very low power mode
+    VeryLowPower,
+}
+
 impl Default for Mode {
     fn default() -> Self {
         Mode::Run(RunMode::FIRC)
@@ -129,6 +285,12 @@ pub enum VeryLowPowerMode {
     SIRC,
 }
 
+/// Frequency of the Fast Internal Reference Clock
+pub const FIRC_FREQ: u32 = 48_000_000;
+
+/// Frequency of the Slow Internal Reference Clock in the (default) high range
+const SIRC_FREQ: u32 = 8_000_000;
+
 /// Clock divider for `CORE_CLK` and `SYS_CLK`.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum DivCore {
@@ -172,6 +334,30 @@ impl Default for DivCore {
     }
 }
 
+impl DivCore {
+    fn from_ratio(ratio: u32) -> Option<Self> {
+        match ratio {
+            1 => Some(DivCore::Div1),
+            2 => Some(DivCore::Div2),
+            3 => Some(DivCore::Div3),
+            4 => Some(DivCore::Div4),
+            5 => Some(DivCore::Div5),
+            6 => Some(DivCore::Div6),
+            7 => Some(DivCore::Div7),
+            8 => Some(DivCore::Div8),
+            9 => Some(DivCore::Div9),
+            10 => Some(DivCore::Div10),
+            11 => Some(DivCore::Div11),
+            12 => Some(DivCore::Div12),
+            13 => Some(DivCore::Div13),
+            14 => Some(DivCore::Div14),
+            15 => Some(DivCore::Div15),
+            16 => Some(DivCore::Div16),
+            _ => None,
+        }
+    }
+}
+
 impl From<DivCore> for u8 {
     fn from(d: DivCore) -> u8 {
         d as u8
@@ -184,7 +370,70 @@ impl From<DivCore> for u32 {
     }
 }
 
+/// Clock divider for `BUS_CLK`.
+///
+/// The available divider values are the same as for `CORE_CLK`.
+pub type DivBus = DivCore;
+
+/// Clock divider for `FLASH_CLK`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DivSlow {
+    /// Divide by 1
+    Div1 = 1,
+    /// Divide by 2
+    Div2 = 2,
+    /// Divide by 3
+    Div3 = 3,
+    /// Divide by 4
+    Div4 = 4,
+    /// Divide by 5
+    Div5 = 5,
+    /// Divide by 6
+    Div6 = 6,
+    /// Divide by 7
+    Div7 = 7,
+    /// Divide by 8
+    Div8 = 8,
+}
+
+impl Default for DivSlow {
+    /// The reset value, keeping `FLASH_CLK` within limits when running from FIRC
+    fn default() -> Self {
+        DivSlow::Div2
+    }
+}
+
+impl DivSlow {
+    fn from_ratio(ratio: u32) -> Option<Self> {
+        match ratio {
+            1 => Some(DivSlow::Div1),
+            2 => Some(DivSlow::Div2),
+            3 => Some(DivSlow::Div3),
+            4 => Some(DivSlow::Div4),
+            5 => Some(DivSlow::Div5),
+            6 => Some(DivSlow::Div6),
+            7 => Some(DivSlow::Div7),
+            8 => Some(DivSlow::Div8),
+            _ => None,
+        }
+    }
+}
+
+impl From<DivSlow> for u8 {
+    fn from(d: DivSlow) -> u8 {
+        d as u8
+    }
+}
+
+impl From<DivSlow> for u32 {
+    fn from(d: DivSlow) -> u32 {
+        d as u32
+    }
+}
+
 /// Clock divider options for system oscillator.
+///
+/// The same divider options are used for the SIRC, FIRC and system PLL outputs.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum SystemOscillatorOutput {
     /// Output disabled
@@ -236,6 +485,82 @@ impl Default for SystemOscillatorOutput {
     }
 }
 
+/// Stop modes entered with `Spc::sleep`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StopMode {
+    /// The core clock is gated while peripherals keep running.
+    ///
+    /// This is VLPW when entered from VLPR.
+    Wait,
+
+    /// Both the system and bus clocks are gated.
+    Stop1,
+
+    /// The system clock is gated while the bus clock keeps running.
+    Stop2,
+
+    /// Very low power stop, only SIRC and LPO may keep running.
+    VeryLowPowerStop,
+}
+
+/// Clocks that can be routed to the CLKOUT pin
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClockOutput {
+    /// `SCG_CLKOUT` sourced from the SCG slow clock (`FLASH_CLK`)
+    ScgSlow,
+
+    /// `SCG_CLKOUT` sourced from an oscillator
+    Scg(Oscillator),
+
+    /// `SOSCDIV2_CLK`
+    Soscdiv2,
+
+    /// `SIRCDIV2_CLK`
+    Sircdiv2,
+
+    /// `FIRCDIV2_CLK`
+    Fircdiv2,
+
+    /// `HCLK`, which is the same as `CORE_CLK`
+    Hclk,
+
+    /// `SPLLDIV2_CLK`
+    Splldiv2,
+
+    /// `BUS_CLK`
+    Bus,
+
+    /// The 128 kHz low power oscillator
+    Lpo128k,
+}
+
+/// Clock divider for the CLKOUT pin
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClockOutputDivider {
+    /// Divide by 1
+    Div1 = 1,
+    /// Divide by 2
+    Div2 = 2,
+    /// Divide by 3
+    Div3 = 3,
+    /// Divide by 4
+    Div4 = 4,
+    /// Divide by 5
+    Div5 = 5,
+    /// Divide by 6
+    Div6 = 6,
+    /// Divide by 7
+    Div7 = 7,
+    /// Divide by 8
+    Div8 = 8,
+}
+
+impl From<ClockOutputDivider> for u8 {
+    fn from(d: ClockOutputDivider) -> u8 {
+        d as u8
+    }
+}
+
 /// The System Clock Generator instance
 pub struct Spc<'a> {
     scg: &'a s32k144::scg::RegisterBlock,
@@ -248,19 +573,465 @@ pub struct Spc<'a> {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Error {
     NoSystemOscillator,
+
+    /// The system PLL is selected as clock source, but `Config::system_pll` is `None`.
+    NoSystemPll,
+
+    /// `SystemPll::prediv` or `SystemPll::mult` is outside the range supported by hardware.
+    SystemPllFactorOutOfRange,
+
+    /// The SPLL reference clock (`SOSC_CLK / prediv`) is not between 8 MHz and 16 MHz.
+    SystemPllReferenceOutOfRange,
+
+    /// `VCO_CLK` is not between 180 MHz and 320 MHz.
+    SystemPllVcoOutOfRange,
+
+    /// `CORE_CLK` is above the maximum frequency allowed in the selected mode.
+    CoreClockTooHigh,
+
+    /// `BUS_CLK` is above the maximum frequency allowed in the selected mode.
+    BusClockTooHigh,
+
+    /// `FLASH_CLK` is above the maximum frequency allowed in the selected mode.
+    FlashClockTooHigh,
+
+    /// The ratio between `CORE_CLK` and `FLASH_CLK` is above 8.
+    CoreToFlashRatioTooHigh,
+
+    /// No clock source and `DivCore` combination gives the requested `CORE_CLK`.
+    CoreClockUnreachable,
+
+    /// No `DivBus` gives the requested `BUS_CLK` from `CORE_CLK`.
+    BusClockUnreachable,
+
+    /// No `DivSlow` gives the requested `FLASH_CLK` from `CORE_CLK`.
+    FlashClockUnreachable,
+
+    /// The oscillator didn't become valid in time, the system keeps running from FIRC.
+    OscillatorTimeout(Oscillator),
+
+    /// The system clock source read back from `SCG_CSR` doesn't match the configured one.
+    ClockSwitchFailed,
+
+    /// The power mode read back from `SMC_PMSTAT` doesn't match the requested one.
+    ModeTransitionFailed,
+
+    /// The selected clock is not running.
+    ClockNotRunning,
+}
+
+/// The clock sources of the SCG
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Oscillator {
+    /// System Oscillator
+    SOSC,
+
+    /// Slow Internal Reference Clock
+    SIRC,
+
+    /// Fast internal Reference Clock
+    FIRC,
+
+    /// Sys PLL
+    SPLL,
+}
+
+impl Oscillator {
+    /// The value of the `SCS` field when this oscillator is the system clock source
+    fn scs(&self) -> u8 {
+        match *self {
+            Oscillator::SOSC => 0b0001,
+            Oscillator::SIRC => 0b0010,
+            Oscillator::FIRC => 0b0011,
+            Oscillator::SPLL => 0b0110,
+        }
+    }
+}
+
+impl Mode {
+    /// Return the oscillator used as system clock source in this mode
+    pub fn system_clock_source(&self) -> Oscillator {
+        match *self {
+            Mode::Run(RunMode::SOSC) => Oscillator::SOSC,
+            Mode::Run(RunMode::SIRC) | Mode::VeryLowPower(VeryLowPowerMode::SIRC) => {
+                Oscillator::SIRC
+            }
+            Mode::Run(RunMode::FIRC) | Mode::HighSpeed(HighSpeedMode::FIRC) => Oscillator::FIRC,
+            Mode::Run(RunMode::SPLL) | Mode::HighSpeed(HighSpeedMode::SPLL) => Oscillator::SPLL,
+        }
+    }
+}
+
+/// Number of times a status flag is polled before giving up
+///
+/// This is well above the start-up time of a crystal oscillator when running from FIRC.
+const STATUS_CHECKS: u32 = 1_000_000;
+
+/// Poll `done` until it returns `true`, or fail with `error` after `STATUS_CHECKS` polls
+fn wait_for<F: Fn() -> bool>(done: F, error: Error) -> Result<(), Error> {
+    for _ in 0..STATUS_CHECKS {
+        if done() {
+            return Ok(());
+        }
+    }
+    Err(error)
+}
+
+/// PMSTAT value in RUN mode
+const PMSTAT_RUN: u8 = 0x01;
+
+/// PMSTAT value in VLPR mode
+const PMSTAT_VLPR: u8 = 0x04;
+
+/// PMSTAT value in HSRUN mode
+const PMSTAT_HSRUN: u8 = 0x80;
+
+/// Maximum frequencies of `CORE_CLK`, `BUS_CLK` and `FLASH_CLK`
+struct Limits {
+    core: u32,
+    bus: u32,
+    flash: u32,
+}
+
+impl Mode {
+    /// The frequency limits as listed in the documentation of each mode
+    fn limits(&self) -> Limits {
+        match *self {
+            Mode::Run(RunMode::SPLL) => Limits {
+                core: 80_000_000,
+                bus: 40_000_000,
+                flash: 26_670_000,
+            },
+            Mode::Run(_) => Limits {
+                core: 80_000_000,
+                bus: 48_000_000,
+                flash: 26_670_000,
+            },
+            Mode::HighSpeed(_) => Limits {
+                core: 112_000_000,
+                bus: 56_000_000,
+                flash: 28_000_000,
+            },
+            Mode::VeryLowPower(_) => Limits {
+                core: 4_000_000,
+                bus: 4_000_000,
+                flash: 1_000_000,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Return the frequency of the selected system clock source before any dividers
+    fn system_clock_freq(&self) -> Result<u32, Error> {
+        match self.mode {
+            Mode::Run(RunMode::SOSC) => self
+                .system_oscillator
+                .clock_frequency()
+                .ok_or(Error::NoSystemOscillator),
+            Mode::Run(RunMode::SIRC) | Mode::VeryLowPower(VeryLowPowerMode::SIRC) => Ok(SIRC_FREQ),
+            Mode::Run(RunMode::FIRC) | Mode::HighSpeed(HighSpeedMode::FIRC) => Ok(FIRC_FREQ),
+            Mode::Run(RunMode::SPLL) | Mode::HighSpeed(HighSpeedMode::SPLL) => self.spll_freq(),
+        }
+    }
+
+    /// Return the frequency of `SPLL_CLK`
+    fn spll_freq(&self) -> Result<u32, Error> {
+        let pll = self.system_pll.ok_or(Error::NoSystemPll)?;
+        let sosc = self
+            .system_oscillator
+            .clock_frequency()
+            .ok_or(Error::NoSystemOscillator)?;
+        Ok(pll.freq(sosc))
+    }
+
+    fn core_freq(&self) -> Result<u32, Error> {
+        Ok(self.system_clock_freq()? / u32::from(self.div_core))
+    }
+
+    fn bus_freq(&self) -> Result<u32, Error> {
+        Ok(self.core_freq()? / u32::from(self.div_bus))
+    }
+
+    fn flash_freq(&self) -> Result<u32, Error> {
+        Ok(self.core_freq()? / u32::from(self.div_slow))
+    }
+
+    /// Check the configuration against the hardware constraints
+    ///
+    /// The core to flash clock ratio is limited by `DivSlow` and needs no check.
+    fn validate(&self) -> Result<(), Error> {
+        if let Some(pll) = self.system_pll {
+            let sosc = self
+                .system_oscillator
+                .clock_frequency()
+                .ok_or(Error::NoSystemOscillator)?;
+            pll.validate(sosc)?;
+        }
+
+        let limits = self.mode.limits();
+        if self.core_freq()? > limits.core {
+            Err(Error::CoreClockTooHigh)
+        } else if self.bus_freq()? > limits.bus {
+            Err(Error::BusClockTooHigh)
+        } else if self.flash_freq()? > limits.flash {
+            Err(Error::FlashClockTooHigh)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Requested clock frequencies in Hz, used by `Config::plan`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ClockTargets {
+    /// Requested `CORE_CLK` and `SYS_CLK` frequency
+    pub core: u32,
+
+    /// Requested `BUS_CLK` frequency
+    pub bus: u32,
+
+    /// Requested `FLASH_CLK` frequency
+    pub flash: u32,
+}
+
+impl Config {
+    /// Find a configuration that gives exactly the requested clock frequencies
+    ///
+    /// The clock sources available in `power_mode` are tried in the order FIRC, SIRC, SOSC and SPLL,
+    /// so the PLL is only used when no other source can give the requested `CORE_CLK`.
+    /// The asynchronous dividers (`soscdiv1`, `soscdiv2` and the SPLL dividers) are left disabled.
+    ///
+    /// No registers are accessed, which makes it possible to check clock choices without hardware.
+    pub fn plan(
+        power_mode: PowerMode,
+        system_oscillator: SystemOscillatorInput,
+        targets: ClockTargets,
+    ) -> Result<Config, Error> {
+        let candidates: &[Mode] = match power_mode {
+            PowerMode::Run => &[
+                Mode::Run(RunMode::FIRC),
+                Mode::Run(RunMode::SIRC),
+                Mode::Run(RunMode::SOSC),
+                Mode::Run(RunMode::SPLL),
+            ],
+            PowerMode::HighSpeed => &[
+                Mode::HighSpeed(HighSpeedMode::FIRC),
+                Mode::HighSpeed(HighSpeedMode::SPLL),
+            ],
+            PowerMode::VeryLowPower => &[Mode::VeryLowPower(VeryLowPowerMode::SIRC)],
+        };
+
+        // The first candidate has the loosest limits of the power mode, which rejects impossible
+        // targets early. The limits of each candidate are checked in `validate`.
+        let limits = candidates[0].limits();
+        if targets.core > limits.core {
+            return Err(Error::CoreClockTooHigh);
+        } else if targets.bus > limits.bus {
+            return Err(Error::BusClockTooHigh);
+        } else if targets.flash > limits.flash {
+            return Err(Error::FlashClockTooHigh);
+        } else if targets.core > targets.flash.saturating_mul(8) {
+            return Err(Error::CoreToFlashRatioTooHigh);
+        }
+
+        let div_bus = exact_ratio(targets.core, targets.bus)
+            .and_then(DivBus::from_ratio)
+            .ok_or(Error::BusClockUnreachable)?;
+        let div_slow = exact_ratio(targets.core, targets.flash)
+            .and_then(DivSlow::from_ratio)
+            .ok_or(Error::FlashClockUnreachable)?;
+
+        // Report the most specific error if no candidate works out
+        let mut error = Error::CoreClockUnreachable;
+        let mut attempt = |config: Config| match config.with_core_freq(targets.core) {
+            Ok(config) => Some(config),
+            Err(Error::CoreClockUnreachable) => None,
+            Err(e) => {
+                error = e;
+                None
+            }
+        };
+
+        for &mode in candidates {
+            let config = Config {
+                mode: mode,
+                div_bus: div_bus,
+                div_slow: div_slow,
+                system_oscillator: system_oscillator,
+                ..Default::default()
+            };
+
+            match mode {
+                Mode::Run(RunMode::SPLL) | Mode::HighSpeed(HighSpeedMode::SPLL) => {
+                    let sosc = match system_oscillator.clock_frequency() {
+                        Some(f) => f,
+                        None => continue,
+                    };
+                    for prediv in SystemPll::PREDIV_MIN..=SystemPll::PREDIV_MAX {
+                        if sosc % u32::from(prediv) != 0 {
+                            continue;
+                        }
+                        for mult in SystemPll::MULT_MIN..=SystemPll::MULT_MAX {
+                            let pll = SystemPll {
+                                prediv: prediv,
+                                mult: mult,
+                                splldiv1: SystemOscillatorOutput::Disable,
+                                splldiv2: SystemOscillatorOutput::Disable,
+                            };
+                            if pll.validate(sosc).is_err() {
+                                continue;
+                            }
+                            let config = Config {
+                                system_pll: Some(pll),
+                                ..config.clone()
+                            };
+                            if let Some(config) = attempt(config) {
+                                return Ok(config);
+                            }
+                        }
+                    }
+                }
+                Mode::Run(RunMode::SOSC) if system_oscillator.clock_frequency().is_none() => (),
+                _ => {
+                    if let Some(config) = attempt(config) {
+                        return Ok(config);
+                    }
+                }
+            }
+        }
+
+        Err(error)
+    }
+
+    /// Select `div_core` to give the requested `CORE_CLK` and validate the result
+    fn with_core_freq(mut self, core: u32) -> Result<Config, Error> {
+        self.div_core = exact_ratio(self.system_clock_freq()?, core)
+            .and_then(DivCore::from_ratio)
+            .ok_or(Error::CoreClockUnreachable)?;
+        self.validate()?;
+        Ok(self)
+    }
+}
+
+/// Return `numerator / denominator` if the division is exact
+fn exact_ratio(numerator: u32, denominator: u32) -> Option<u32> {
+    if denominator != 0 && numerator % denominator == 0 {
+        Some(numerator / denominator)
+    } else {
+        None
+    }
+}
+
+/// Switch the system clock to SIRC and disable the sources that are not allowed in VLPR and VLPS
+fn run_from_sirc_only(scg: &s32k144::scg::RegisterBlock) -> Result<(), Error> {
+    scg.rccr.modify(|_, w| w.scs()._0010());
+    wait_for(
+        || scg.csr.read().scs().bits() == Oscillator::SIRC.scs(),
+        Error::ClockSwitchFailed,
+    )?;
+    scg.spllcsr
+        .modify(|_, w| w.spllcm()._0().spllerr().clear_bit());
+    scg.sosccsr
+        .modify(|_, w| w.sosccm()._0().soscerr().clear_bit());
+    scg.spllcsr
+        .modify(|_, w| w.spllen()._0().spllerr().clear_bit());
+    scg.sosccsr
+        .modify(|_, w| w.soscen()._0().soscerr().clear_bit());
+    scg.firccsr.modify(|_, w| w.fircen()._0());
+    scg.sirccsr.modify(|_, w| w.sirclpen()._1());
+    Ok(())
+}
+
+/// Return the frequency of an asynchronous divider output if enabled
+fn divided_freq(freq: u32, div: SystemOscillatorOutput) -> Option<u32> {
+    match div {
+        SystemOscillatorOutput::Disable => None,
+        oscillator_output => {
+            let div = 1 << (usize::from(oscillator_output) - 1);
+            Some(freq / div)
+        }
+    }
 }
 
 impl<'a> Spc<'a> {
     /// Initialized the System Clock Generator with the given configs
+    ///
+    /// If an oscillator fails to start, `Error::OscillatorTimeout` is returned and the system is
+    /// left running from FIRC.
     pub fn init(
         scg: &'a s32k144::scg::RegisterBlock,
         smc: &'a s32k144::smc::RegisterBlock,
         pmc: &'a s32k144::pmc::RegisterBlock,
         config: Config,
     ) -> Result<Self, Error> {
-        match config.system_oscillator {
+        config.validate()?;
+
+        // Leaving VLPR or HSRUN switches to the RCCR clock source. SIRC is the only one running in
+        // VLPR, and the RCCR source may be the SPLL running above the RUN limits in HSRUN, so RUN
+        // is entered from HSRUN on FIRC with dividers allowed in RUN.
+        let pmstat = smc.pmstat.read().pmstat().bits();
+        if pmstat == PMSTAT_VLPR {
+            scg.rccr.modify(|_, w| w.scs()._0010());
+        } else if pmstat == PMSTAT_HSRUN {
+            scg.firccsr.modify(|_, w| w.fircen()._1());
+            wait_for(
+                || scg.firccsr.read().fircvld().is_1(),
+                Error::OscillatorTimeout(Oscillator::FIRC),
+            )?;
+            scg.rccr.modify(|_, w| unsafe {
+                w.divcore()
+                    .bits(u8::from(DivCore::Div1) - 1)
+                    .divbus()
+                    .bits(u8::from(DivBus::Div1) - 1)
+                    .divslow()
+                    .bits(u8::from(DivSlow::Div2) - 1)
+                    .scs()
+                    ._0011()
+            });
+        }
+
+        // The RCCR settings are only in use in RUN mode
+        smc.pmctrl.modify(|_, w| w.runm()._00());
+        wait_for(
+            || smc.pmstat.read().pmstat().bits() == PMSTAT_RUN,
+            Error::ModeTransitionFailed,
+        )?;
+
+        // Run from FIRC while the other sources are reconfigured, as the active source can't be disabled.
+        if scg.csr.read().scs().bits() != Oscillator::FIRC.scs() {
+            scg.firccsr.modify(|_, w| w.fircen()._1());
+            wait_for(
+                || scg.firccsr.read().fircvld().is_1(),
+                Error::OscillatorTimeout(Oscillator::FIRC),
+            )?;
+            scg.rccr.modify(|_, w| w.scs()._0011());
+            wait_for(
+                || scg.csr.read().scs().bits() == Oscillator::FIRC.scs(),
+                Error::ClockSwitchFailed,
+            )?;
+        }
+
+        // The clock monitors must be disabled before the clocks they monitor
+        scg.spllcsr
+            .modify(|_, w| w.spllcm()._0().spllerr().clear_bit());
+        scg.sosccsr
+            .modify(|_, w| w.sosccm()._0().soscerr().clear_bit());
+
+        // The PLL must be disabled before the system oscillator is reconfigured
+        scg.spllcsr
+            .modify(|_, w| w.spllen()._0().spllerr().clear_bit());
+
+        // Only SIRC is available in VLPR, so the other sources are left disabled
+        let (system_oscillator, system_pll) = match config.mode {
+            Mode::VeryLowPower(_) => (SystemOscillatorInput::None, None),
+            _ => (config.system_oscillator, config.system_pll),
+        };
+
+        match system_oscillator {
             SystemOscillatorInput::None => {
-                scg.sosccsr.modify(|_, w| w.soscen()._0());
+                scg.sosccsr
+                    .modify(|_, w| w.soscen()._0().soscerr().clear_bit());
             }
             SystemOscillatorInput::Crystal(f) => {
                 scg.sosccfg.modify(|_, w| w.erefs()._1().hgo()._1());
@@ -270,62 +1041,177 @@ impl<'a> Spc<'a> {
                 } else {
                     scg.sosccfg.modify(|_, w| w.range()._10());
                 }
-                scg.sosccsr.modify(|_, w| w.soscen()._1());
+                scg.sosccsr
+                    .modify(|_, w| w.soscen()._1().soscerr().clear_bit());
             }
             SystemOscillatorInput::Reference(_) => {
-                scg.sosccsr.modify(|_, w| w.soscen()._1());
+                scg.sosccsr
+                    .modify(|_, w| w.soscen()._1().soscerr().clear_bit());
                 scg.sosccfg.modify(|_, w| w.erefs()._1());
             }
         }
 
-        // TODO: wait untill system oscillator is valid if configured
+        if system_oscillator != SystemOscillatorInput::None {
+            if let Err(e) = wait_for(
+                || scg.sosccsr.read().soscvld().is_1(),
+                Error::OscillatorTimeout(Oscillator::SOSC),
+            ) {
+                scg.sosccsr
+                    .modify(|_, w| w.soscen()._0().soscerr().clear_bit());
+                return Err(e);
+            }
+
+            scg.sosccsr.modify(|_, w| {
+                w.sosccmre()
+                    .bit(config.sosc_monitor == ClockMonitor::Reset)
+                    .sosccm()
+                    .bit(config.sosc_monitor != ClockMonitor::Disabled)
+                    .soscerr()
+                    .clear_bit()
+            });
+        }
 
         scg.soscdiv
             .modify(|_, w| w.soscdiv1().bits(config.soscdiv1.into()));
         scg.soscdiv
             .modify(|_, w| w.soscdiv2().bits(config.soscdiv2.into()));
 
+        if config.sircdiv1 != SystemOscillatorOutput::Disable
+            || config.sircdiv2 != SystemOscillatorOutput::Disable
+            || config.mode.system_clock_source() == Oscillator::SIRC
+        {
+            wait_for(
+                || scg.sirccsr.read().sircvld().is_1(),
+                Error::OscillatorTimeout(Oscillator::SIRC),
+            )?;
+        }
+
+        scg.sircdiv.modify(|_, w| {
+            w.sircdiv1()
+                .bits(config.sircdiv1.into())
+                .sircdiv2()
+                .bits(config.sircdiv2.into())
+        });
+        scg.fircdiv.modify(|_, w| {
+            w.fircdiv1()
+                .bits(config.fircdiv1.into())
+                .fircdiv2()
+                .bits(config.fircdiv2.into())
+        });
+
+        if let Some(pll) = system_pll {
+            scg.splldiv
+                .modify(|_, w| w.splldiv1().bits(pll.splldiv1.into()));
+            scg.splldiv
+                .modify(|_, w| w.splldiv2().bits(pll.splldiv2.into()));
+            scg.spllcfg.write(|w| unsafe {
+                w.prediv()
+                    .bits(pll.prediv - SystemPll::PREDIV_MIN)
+                    .mult()
+                    .bits(pll.mult - SystemPll::MULT_MIN)
+            });
+
+            scg.spllcsr
+                .modify(|_, w| w.spllen()._1().spllerr().clear_bit());
+            if let Err(e) = wait_for(
+                || scg.spllcsr.read().spllvld().is_1(),
+                Error::OscillatorTimeout(Oscillator::SPLL),
+            ) {
+                scg.spllcsr
+                    .modify(|_, w| w.spllen()._0().spllerr().clear_bit());
+                return Err(e);
+            }
+
+            scg.spllcsr.modify(|_, w| {
+                w.spllcmre()
+                    .bit(config.spll_monitor == ClockMonitor::Reset)
+                    .spllcm()
+                    .bit(config.spll_monitor != ClockMonitor::Disabled)
+                    .spllerr()
+                    .clear_bit()
+            });
+        }
+
         // Allowing a transition into HSRUN or VLPR
         smc.pmprot.write(|w| w.ahsrun()._1().avlp()._1());
 
         // When configuring this, we should already have configured the source and make sure it's valid.
         match config.mode {
             Mode::Run(mode) => {
-                // Set the dividers
-                scg.rccr
-                    .modify(|_, w| w.divcore().bits(u8::from(config.div_core) - 1));
-                match mode {
-                    RunMode::SOSC => {
-                        if let SystemOscillatorInput::None = config.system_oscillator {
-                            return Err(Error::NoSystemOscillator);
-                        }
-                        scg.rccr.modify(|_, w| w.scs()._0001());
+                // Set the dividers and clock source in a single write
+                scg.rccr.modify(|_, w| unsafe {
+                    let w = w
+                        .divcore()
+                        .bits(u8::from(config.div_core) - 1)
+                        .divbus()
+                        .bits(u8::from(config.div_bus) - 1)
+                        .divslow()
+                        .bits(u8::from(config.div_slow) - 1);
+                    match mode {
+                        RunMode::SOSC => w.scs()._0001(),
+                        RunMode::SIRC => w.scs()._0010(),
+                        RunMode::FIRC => w.scs()._0011(),
+                        RunMode::SPLL => w.scs()._0110(),
                     }
-                    RunMode::SIRC => {
-                        unimplemented!("Mode::Run(RunMode::SIRC) is is not supported yet");
-                        // scg.rccr.modify(|_, w| w.scs()._0010());
-                    }
-                    RunMode::FIRC => scg.rccr.modify(|_, w| w.scs()._0011()),
-                    RunMode::SPLL => {
-                        unimplemented!("Mode::Run(RunMode::SPLL) is is not supported yet");
-                        // scg.rccr.modify(|_, w| w.scs()._0110())
-                    }
-                }
-                // transition into run mode
-                smc.pmctrl.modify(|_, w| w.runm()._00());
-                while smc.pmstat.read().pmstat().bits() != 0000_001 {}
+                });
+                wait_for(
+                    || scg.csr.read().scs().bits() == config.mode.system_clock_source().scs(),
+                    Error::ClockSwitchFailed,
+                )?;
             }
-            Mode::HighSpeed(_mode) => {
-                // Set the dividers
-                scg.hccr
-                    .modify(|_, w| w.divcore().bits(u8::from(config.div_core)));
-                unimplemented!("High speed more is not supported yet");
+            Mode::HighSpeed(mode) => {
+                // Set the dividers and clock source used once HSRUN is entered
+                scg.hccr.modify(|_, w| unsafe {
+                    let w = w
+                        .divcore()
+                        .bits(u8::from(config.div_core) - 1)
+                        .divbus()
+                        .bits(u8::from(config.div_bus) - 1)
+                        .divslow()
+                        .bits(u8::from(config.div_slow) - 1);
+                    match mode {
+                        HighSpeedMode::FIRC => w.scs()._0011(),
+                        HighSpeedMode::SPLL => w.scs()._0110(),
+                    }
+                });
+
+                // transition into high speed run mode
+                smc.pmctrl.modify(|_, w| w.runm()._11());
+                wait_for(
+                    || smc.pmstat.read().pmstat().bits() == PMSTAT_HSRUN,
+                    Error::ModeTransitionFailed,
+                )?;
+                wait_for(
+                    || scg.csr.read().scs().bits() == config.mode.system_clock_source().scs(),
+                    Error::ClockSwitchFailed,
+                )?;
             }
-            Mode::VeryLowPower(_mode) => {
-                // Set the dividers
-                scg.vccr
-                    .modify(|_, w| w.divcore().bits(u8::from(config.div_core)));
-                unimplemented!("Very low power mode is not supported yet");
+            Mode::VeryLowPower(mode) => {
+                run_from_sirc_only(scg)?;
+
+                // Set the dividers and clock source used once VLPR is entered
+                scg.vccr.modify(|_, w| unsafe {
+                    let w = w
+                        .divcore()
+                        .bits(u8::from(config.div_core) - 1)
+                        .divbus()
+                        .bits(u8::from(config.div_bus) - 1)
+                        .divslow()
+                        .bits(u8::from(config.div_slow) - 1);
+                    match mode {
+                        VeryLowPowerMode::SIRC => w.scs()._0010(),
+                    }
+                });
+
+                // The bias generator must be enabled in VLPR
+                pmc::Pmc::init(pmc).set_bias(true);
+
+                // transition into very low power run mode
+                smc.pmctrl.modify(|_, w| w.runm()._10());
+                wait_for(
+                    || smc.pmstat.read().pmstat().bits() == PMSTAT_VLPR,
+                    Error::ModeTransitionFailed,
+                )?;
             }
         }
 
@@ -337,52 +1223,540 @@ impl<'a> Spc<'a> {
         })
     }
 
-    /// Return the frequency of socdiv1 clock if running
-    pub fn soscdiv1_freq(&self) -> Option<u32> {
-        let freq = self.config.system_oscillator.clock_frequency()?;
-        match self.config.soscdiv1 {
-            SystemOscillatorOutput::Disable => None,
-            oscillator_output => {
-                let div = 1 << (usize::from(oscillator_output) - 1);
-                Some(freq / div)
+    /// Enter RUN mode with the given clock source and dividers
+    ///
+    /// The other settings, including the system PLL, are kept. The returned `Spc` reports the
+    /// new frequencies, while `self` keeps describing the previous mode.
+    pub fn enter_run(
+        &self,
+        mode: RunMode,
+        div_core: DivCore,
+        div_bus: DivBus,
+        div_slow: DivSlow,
+    ) -> Result<Spc<'a>, Error> {
+        self.reconfigure(Config {
+            mode: Mode::Run(mode),
+            div_core: div_core,
+            div_bus: div_bus,
+            div_slow: div_slow,
+            ..self.config.clone()
+        })
+    }
+
+    /// Enter HSRUN mode with the given clock source and dividers
+    ///
+    /// HSRUN is entered through RUN mode. The other settings, including the system PLL, are kept.
+    /// The returned `Spc` reports the new frequencies, while `self` keeps describing the previous mode.
+    pub fn enter_hsrun(
+        &self,
+        mode: HighSpeedMode,
+        div_core: DivCore,
+        div_bus: DivBus,
+        div_slow: DivSlow,
+    ) -> Result<Spc<'a>, Error> {
+        self.reconfigure(Config {
+            mode: Mode::HighSpeed(mode),
+            div_core: div_core,
+            div_bus: div_bus,
+            div_slow: div_slow,
+            ..self.config.clone()
+        })
+    }
+
+    /// Enter VLPR mode with the given clock source and dividers
+    ///
+    /// VLPR is entered through RUN mode. SOSC, FIRC and the system PLL are disabled while in VLPR,
+    /// and are enabled again by `enter_run` or `enter_hsrun`. The returned `Spc` reports the new
+    /// frequencies, while `self` keeps describing the previous mode.
+    pub fn enter_vlpr(
+        &self,
+        mode: VeryLowPowerMode,
+        div_core: DivCore,
+        div_bus: DivBus,
+        div_slow: DivSlow,
+    ) -> Result<Spc<'a>, Error> {
+        self.reconfigure(Config {
+            mode: Mode::VeryLowPower(mode),
+            div_core: div_core,
+            div_bus: div_bus,
+            div_slow: div_slow,
+            ..self.config.clone()
+        })
+    }
+
+    /// Apply a new configuration, transitioning through RUN mode as required
+    pub fn reconfigure(&self, config: Config) -> Result<Spc<'a>, Error> {
+        Spc::init(self.scg, self.smc, self.pmc, config)
+    }
+
+    /// Enter a stop mode and sleep until `wakeup` is triggered
+    ///
+    /// The `wakeup` interrupt is enabled in the NVIC, and must be configured in its peripheral
+    /// to fire in the selected stop mode. The clock configuration in use before sleeping is restored
+    /// before this function returns.
+    ///
+    /// `StopMode::Stop1` and `StopMode::Stop2` can only be entered from RUN, `StopMode::VeryLowPowerStop`
+    /// only from RUN or VLPR. `Error::ModeTransitionFailed` is returned otherwise.
+    pub fn sleep(
+        &self,
+        mode: StopMode,
+        scb: &mut SCB,
+        nvic: &mut NVIC,
+        wakeup: s32k144::Interrupt,
+    ) -> Result<(), Error> {
+        let power_mode = self.config.mode.power_mode();
+        match (mode, power_mode) {
+            (StopMode::Wait, _) => (),
+            (StopMode::Stop1, PowerMode::Run) | (StopMode::Stop2, PowerMode::Run) => (),
+            (StopMode::VeryLowPowerStop, PowerMode::Run)
+            | (StopMode::VeryLowPowerStop, PowerMode::VeryLowPower) => (),
+            _ => return Err(Error::ModeTransitionFailed),
+        }
+
+        match mode {
+            StopMode::Wait => scb.clear_sleepdeep(),
+            StopMode::Stop1 | StopMode::Stop2 => {
+                self.smc.stopctrl.modify(|_, w| unsafe {
+                    w.stopo()
+                        .bits(if mode == StopMode::Stop1 { 0b01 } else { 0b10 })
+                });
+                self.smc
+                    .pmctrl
+                    .modify(|_, w| unsafe { w.stopm().bits(0b000) });
+                scb.set_sleepdeep();
+            }
+            StopMode::VeryLowPowerStop => {
+                if power_mode == PowerMode::Run {
+                    run_from_sirc_only(self.scg)?;
+                }
+                self.set_bias(true);
+                self.smc
+                    .pmctrl
+                    .modify(|_, w| unsafe { w.stopm().bits(0b010) });
+                scb.set_sleepdeep();
+            }
+        }
+
+        // Make sure the mode is written before the core goes to sleep
+        let _pmctrl = self.smc.pmctrl.read();
+
+        nvic.enable(wakeup);
+        cortex_m::asm::wfi();
+        scb.clear_sleepdeep();
+
+        if mode == StopMode::VeryLowPowerStop && power_mode == PowerMode::Run {
+            self.reconfigure(self.config.clone())?;
+        }
+
+        Ok(())
+    }
+
+    /// Keep SIRC running in stop modes, e.g. to clock the LPTMR while sleeping
+    pub fn set_sirc_in_stop(&self, enable: bool) {
+        self.scg.sirccsr.modify(|_, w| w.sircsten().bit(enable));
+    }
+
+    /// Enable or disable the PMC bias generator
+    ///
+    /// The bias must be enabled before entering VLPR or VLPS.
+    pub fn set_bias(&self, enable: bool) {
+        self.pmc().set_bias(enable);
+    }
+
+    /// Return the Power Management Controller
+    pub fn pmc(&self) -> pmc::Pmc<'a> {
+        pmc::Pmc::init(self.pmc)
+    }
+
+    /// Return the oscillator whose clock monitor has detected loss of clock, if any
+    ///
+    /// Loss of the system oscillator is reported before loss of the system PLL, as the PLL
+    /// is clocked from the system oscillator.
+    pub fn clock_loss(&self) -> Option<Oscillator> {
+        if self.scg.sosccsr.read().soscerr().bit_is_set() {
+            Some(Oscillator::SOSC)
+        } else if self.scg.spllcsr.read().spllerr().bit_is_set() {
+            Some(Oscillator::SPLL)
+        } else {
+            None
+        }
+    }
+
+    /// Fall back to RUN mode on FIRC after loss of clock
+    ///
+    /// The clock monitor flags are cleared and the system oscillator and system PLL are disabled.
+    /// `BUS_CLK` and `FLASH_CLK` are set to 48 MHz and 24 MHz, and the returned `Spc` reports the new frequencies.
+    pub fn recover_from_clock_loss(&self) -> Result<Spc<'a>, Error> {
+        self.scg
+            .sosccsr
+            .modify(|_, w| w.sosccm()._0().soscerr().set_bit());
+        self.scg
+            .spllcsr
+            .modify(|_, w| w.spllcm()._0().spllerr().set_bit());
+
+        self.reconfigure(Config {
+            mode: Mode::Run(RunMode::FIRC),
+            div_core: DivCore::Div1,
+            div_bus: DivBus::Div1,
+            div_slow: DivSlow::Div2,
+            system_oscillator: SystemOscillatorInput::None,
+            soscdiv1: SystemOscillatorOutput::Disable,
+            soscdiv2: SystemOscillatorOutput::Disable,
+            system_pll: None,
+            sosc_monitor: ClockMonitor::Disabled,
+            spll_monitor: ClockMonitor::Disabled,
+            ..self.config.clone()
+        })
+    }
+
+    /// Route a clock to the CLKOUT pin through the given divider
+    ///
+    /// CLKOUT is muxed to PTE10 (ALT2), which is available on the EVB headers, so the clocks can be
+    /// measured with a scope. The expected frequency on the pin is returned.
+    pub fn enable_clock_output(
+        &self,
+        sim: &s32k144::sim::RegisterBlock,
+        porte: &s32k144::porte::RegisterBlock,
+        _pcc_porte: &pcc::PortE,
+        output: ClockOutput,
+        divider: ClockOutputDivider,
+    ) -> Result<u32, Error> {
+        let (freq, clkoutsel) = match output {
+            ClockOutput::ScgSlow => (Some(self.flash_freq()), 0b0000),
+            ClockOutput::Scg(oscillator) => {
+                let freq = match oscillator {
+                    Oscillator::SOSC => self.sosc_freq(),
+                    Oscillator::SIRC => self.sirc_freq(),
+                    Oscillator::FIRC => self.firc_freq(),
+                    Oscillator::SPLL => self.spll_freq(),
+                };
+                (freq, 0b0000)
             }
+            ClockOutput::Soscdiv2 => (self.soscdiv2_freq(), 0b0010),
+            ClockOutput::Sircdiv2 => (self.sircdiv2_freq(), 0b0100),
+            ClockOutput::Fircdiv2 => (self.fircdiv2_freq(), 0b0110),
+            ClockOutput::Hclk => (Some(self.core_freq()), 0b1000),
+            ClockOutput::Splldiv2 => (self.splldiv2_freq(), 0b1010),
+            ClockOutput::Bus => (Some(self.bus_freq()), 0b1100),
+            ClockOutput::Lpo128k => (Some(128_000), 0b1110),
+        };
+        let freq = freq.ok_or(Error::ClockNotRunning)?;
+
+        match output {
+            ClockOutput::ScgSlow => self
+                .scg
+                .clkoutcnfg
+                .write(|w| unsafe { w.clkoutsel().bits(0b0000) }),
+            ClockOutput::Scg(oscillator) => self
+                .scg
+                .clkoutcnfg
+                .write(|w| unsafe { w.clkoutsel().bits(oscillator.scs()) }),
+            _ => (),
+        }
+
+        // The output must be disabled while the source and divider are changed
+        sim.chipctl.modify(|_, w| w.clkouten().clear_bit());
+        sim.chipctl.modify(|_, w| unsafe {
+            w.clkoutsel()
+                .bits(clkoutsel)
+                .clkoutdiv()
+                .bits(u8::from(divider) - 1)
+        });
+        sim.chipctl.modify(|_, w| w.clkouten().set_bit());
+
+        porte.pcr10.modify(|_, w| w.mux()._010());
+
+        Ok(freq / u32::from(u8::from(divider)))
+    }
+
+    /// Stop driving the CLKOUT pin
+    pub fn disable_clock_output(
+        &self,
+        sim: &s32k144::sim::RegisterBlock,
+        porte: &s32k144::porte::RegisterBlock,
+    ) {
+        sim.chipctl.modify(|_, w| w.clkouten().clear_bit());
+        porte.pcr10.modify(|_, w| w.mux()._000());
+    }
+
+    /// Return the configuration in use
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Return true if the oscillator is running in the current mode
+    fn is_running(&self, oscillator: Oscillator) -> bool {
+        match self.config.mode {
+            Mode::VeryLowPower(_) => oscillator == Oscillator::SIRC,
+            _ => true,
+        }
+    }
+
+    /// Return the frequency of `SOSC_CLK` if running
+    pub fn sosc_freq(&self) -> Option<u32> {
+        if self.is_running(Oscillator::SOSC) {
+            self.config.system_oscillator.clock_frequency()
+        } else {
+            None
         }
     }
 
+    /// Return the frequency of socdiv1 clock if running
+    pub fn soscdiv1_freq(&self) -> Option<u32> {
+        divided_freq(self.sosc_freq()?, self.config.soscdiv1)
+    }
+
     /// Return the frequency of socdiv2 clock if running
     pub fn soscdiv2_freq(&self) -> Option<u32> {
-        let freq = self.config.system_oscillator.clock_frequency()?;
-        match self.config.soscdiv2 {
-            SystemOscillatorOutput::Disable => None,
-            oscillator_output => {
-                let div = 1 << (usize::from(oscillator_output) - 1);
-                Some(freq / div)
-            }
+        divided_freq(self.sosc_freq()?, self.config.soscdiv2)
+    }
+
+    /// Return the frequency of `SPLL_CLK` if running
+    pub fn spll_freq(&self) -> Option<u32> {
+        if self.is_running(Oscillator::SPLL) {
+            self.config.spll_freq().ok()
+        } else {
+            None
         }
     }
 
-    /// Return the frequency of `CORE_CLK` in MHz
+    /// Return the frequency of splldiv1 clock if running
+    pub fn splldiv1_freq(&self) -> Option<u32> {
+        let freq = self.spll_freq()?;
+        divided_freq(freq, self.config.system_pll?.splldiv1)
+    }
+
+    /// Return the frequency of splldiv2 clock if running
+    pub fn splldiv2_freq(&self) -> Option<u32> {
+        let freq = self.spll_freq()?;
+        divided_freq(freq, self.config.system_pll?.splldiv2)
+    }
+
+    /// Return the frequency of `SIRC_CLK` if running
+    pub fn sirc_freq(&self) -> Option<u32> {
+        Some(SIRC_FREQ)
+    }
+
+    /// Return the frequency of sircdiv1 clock if running
+    pub fn sircdiv1_freq(&self) -> Option<u32> {
+        divided_freq(self.sirc_freq()?, self.config.sircdiv1)
+    }
+
+    /// Return the frequency of sircdiv2 clock if running
+    pub fn sircdiv2_freq(&self) -> Option<u32> {
+        divided_freq(self.sirc_freq()?, self.config.sircdiv2)
+    }
+
+    /// Return the frequency of `FIRC_CLK` if running
+    pub fn firc_freq(&self) -> Option<u32> {
+        if self.is_running(Oscillator::FIRC) {
+            Some(FIRC_FREQ)
+        } else {
+            None
+        }
+    }
+
+    /// Return the frequency of fircdiv1 clock if running
+    pub fn fircdiv1_freq(&self) -> Option<u32> {
+        divided_freq(self.firc_freq()?, self.config.fircdiv1)
+    }
+
+    /// Return the frequency of fircdiv2 clock if running
+    pub fn fircdiv2_freq(&self) -> Option<u32> {
+        divided_freq(self.firc_freq()?, self.config.fircdiv2)
+    }
+
+    /// Return the frequency of `CORE_CLK` in Hz
     pub fn core_freq(&self) -> u32 {
-        match self.config.mode {
-            Mode::Run(mode) => match mode {
-                RunMode::SOSC => {
-                    let freq = self.config.system_oscillator.clock_frequency().unwrap();
-                    freq / u32::from(self.config.div_core)
-                }
-                RunMode::SIRC => {
-                    unimplemented!("Mode::Run(RunMode::SIRC) is is not supported yet");
-                }
-                RunMode::FIRC => 48_000_000 / u32::from(self.config.div_core),
-                RunMode::SPLL => {
-                    unimplemented!("Mode::Run(RunMode::SPLL) is is not supported yet");
-                }
-            },
-            Mode::HighSpeed(_mode) => {
-                unimplemented!("High speed more is not supported yet");
-            }
-            Mode::VeryLowPower(_mode) => {
-                unimplemented!("Very low power mode is not supported yet");
-            }
+        // The configuration is validated in `init`, so the frequency is always known
+        self.config.core_freq().unwrap()
+    }
+
+    /// Return the frequency of `BUS_CLK` in Hz
+    pub fn bus_freq(&self) -> u32 {
+        self.config.bus_freq().unwrap()
+    }
+
+    /// Return the frequency of `FLASH_CLK` in Hz
+    pub fn flash_freq(&self) -> u32 {
+        self.config.flash_freq().unwrap()
+    }
+
+    /// Return the frequency of every clock at once
+    pub fn clocks(&self) -> ClockSnapshot {
+        ClockSnapshot {
+            core: self.core_freq(),
+            bus: self.bus_freq(),
+            flash: self.flash_freq(),
+            sosc: self.sosc_freq(),
+            soscdiv1: self.soscdiv1_freq(),
+            soscdiv2: self.soscdiv2_freq(),
+            sirc: self.sirc_freq(),
+            sircdiv1: self.sircdiv1_freq(),
+            sircdiv2: self.sircdiv2_freq(),
+            firc: self.firc_freq(),
+            fircdiv1: self.fircdiv1_freq(),
+            fircdiv2: self.fircdiv2_freq(),
+            spll: self.spll_freq(),
+            splldiv1: self.splldiv1_freq(),
+            splldiv2: self.splldiv2_freq(),
+        }
+    }
+}
+
+/// The frequency in Hz of every clock generated by the SCG
+///
+/// Clocks that are not running are `None`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ClockSnapshot {
+    /// `CORE_CLK` and `SYS_CLK`
+    pub core: u32,
+    /// `BUS_CLK`
+    pub bus: u32,
+    /// `FLASH_CLK`
+    pub flash: u32,
+    /// `SOSC_CLK`
+    pub sosc: Option<u32>,
+    /// `SOSCDIV1_CLK`
+    pub soscdiv1: Option<u32>,
+    /// `SOSCDIV2_CLK`
+    pub soscdiv2: Option<u32>,
+    /// `SIRC_CLK`
+    pub sirc: Option<u32>,
+    /// `SIRCDIV1_CLK`
+    pub sircdiv1: Option<u32>,
+    /// `SIRCDIV2_CLK`
+    pub sircdiv2: Option<u32>,
+    /// `FIRC_CLK`
+    pub firc: Option<u32>,
+    /// `FIRCDIV1_CLK`
+    pub fircdiv1: Option<u32>,
+    /// `FIRCDIV2_CLK`
+    pub fircdiv2: Option<u32>,
+    /// `SPLL_CLK`
+    pub spll: Option<u32>,
+    /// `SPLLDIV1_CLK`
+    pub splldiv1: Option<u32>,
+    /// `SPLLDIV2_CLK`
+    pub splldiv2: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(core: u32, bus: u32, flash: u32) -> ClockTargets {
+        ClockTargets {
+            core: core,
+            bus: bus,
+            flash: flash,
         }
     }
+
+    fn plan_run(core: u32, bus: u32, flash: u32) -> Result<Config, Error> {
+        Config::plan(
+            PowerMode::Run,
+            SystemOscillatorInput::Crystal(8_000_000),
+            targets(core, bus, flash),
+        )
+    }
+
+    #[test]
+    fn plan_80mhz_from_crystal_uses_spll() {
+        let config = plan_run(80_000_000, 40_000_000, 20_000_000).unwrap();
+        assert_eq!(config.mode, Mode::Run(RunMode::SPLL));
+        assert_eq!(config.core_freq(), Ok(80_000_000));
+        assert_eq!(config.bus_freq(), Ok(40_000_000));
+        assert_eq!(config.flash_freq(), Ok(20_000_000));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn plan_48mhz_prefers_firc() {
+        let config = plan_run(48_000_000, 48_000_000, 24_000_000).unwrap();
+        assert_eq!(config.mode, Mode::Run(RunMode::FIRC));
+        assert!(config.system_pll.is_none());
+    }
+
+    #[test]
+    fn plan_hsrun_112mhz() {
+        let config = Config::plan(
+            PowerMode::HighSpeed,
+            SystemOscillatorInput::Crystal(8_000_000),
+            targets(112_000_000, 56_000_000, 28_000_000),
+        )
+        .unwrap();
+        assert_eq!(config.mode, Mode::HighSpeed(HighSpeedMode::SPLL));
+        assert_eq!(config.core_freq(), Ok(112_000_000));
+        assert_eq!(config.bus_freq(), Ok(56_000_000));
+        assert_eq!(config.flash_freq(), Ok(28_000_000));
+    }
+
+    #[test]
+    fn plan_vlpr() {
+        let config = Config::plan(
+            PowerMode::VeryLowPower,
+            SystemOscillatorInput::None,
+            targets(4_000_000, 4_000_000, 1_000_000),
+        )
+        .unwrap();
+        assert_eq!(config.mode, Mode::VeryLowPower(VeryLowPowerMode::SIRC));
+        assert_eq!(config.core_freq(), Ok(4_000_000));
+        assert_eq!(config.bus_freq(), Ok(4_000_000));
+        assert_eq!(config.flash_freq(), Ok(1_000_000));
+    }
+
+    #[test]
+    fn plan_vlpr_above_limit() {
+        let result = Config::plan(
+            PowerMode::VeryLowPower,
+            SystemOscillatorInput::None,
+            targets(8_000_000, 4_000_000, 1_000_000),
+        );
+        assert_eq!(result.err(), Some(Error::CoreClockTooHigh));
+    }
+
+    #[test]
+    fn plan_clock_too_high() {
+        assert_eq!(
+            plan_run(100_000_000, 50_000_000, 25_000_000).err(),
+            Some(Error::CoreClockTooHigh)
+        );
+        assert_eq!(
+            plan_run(80_000_000, 80_000_000, 20_000_000).err(),
+            Some(Error::BusClockTooHigh)
+        );
+        assert_eq!(
+            plan_run(48_000_000, 48_000_000, 48_000_000).err(),
+            Some(Error::FlashClockTooHigh)
+        );
+    }
+
+    #[test]
+    fn plan_core_to_flash_ratio_too_high() {
+        assert_eq!(
+            plan_run(80_000_000, 40_000_000, 8_000_000).err(),
+            Some(Error::CoreToFlashRatioTooHigh)
+        );
+    }
+
+    #[test]
+    fn plan_unreachable() {
+        assert_eq!(
+            plan_run(48_000_000, 7_000_000, 24_000_000).err(),
+            Some(Error::BusClockUnreachable)
+        );
+        assert_eq!(
+            plan_run(48_000_000, 24_000_000, 7_000_000).err(),
+            Some(Error::FlashClockUnreachable)
+        );
+
+        // Without a crystal only FIRC and SIRC are available
+        let result = Config::plan(
+            PowerMode::Run,
+            SystemOscillatorInput::None,
+            targets(7_000_000, 7_000_000, 7_000_000),
+        );
+        assert_eq!(result.err(), Some(Error::CoreClockUnreachable));
+    }
 }